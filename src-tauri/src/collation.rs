@@ -0,0 +1,196 @@
+use pinyin::ToPinyin;
+use rusqlite::{params, Connection};
+use wana_kana::ConvertJapanese;
+
+/// Precomputed keys stored next to a title so SQL can sort and match it phonetically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleKeys {
+    /// Syllables separated by spaces, e.g. `zang song de fu li lian`.
+    pub sort_key: String,
+    /// Full romanization followed by initials, e.g. `zangsongdefulilian zsdfll`.
+    pub search_key: String,
+}
+
+fn is_kana(ch: char) -> bool {
+    // U+30FB (・) separates words in katakana titles rather than spelling anything.
+    ch != '\u{30FB}' && matches!(ch, '\u{3040}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}')
+}
+
+/// Splits a title into romanized tokens: one per hanzi (by pinyin), one per run of kana
+/// (by romaji) and one per run of other letters or digits. Kanji are read as Chinese.
+fn romanize(title: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut kana = String::new();
+    let mut word = String::new();
+
+    let flush = |buffer: &mut String, tokens: &mut Vec<String>, is_kana_run: bool| {
+        if buffer.is_empty() {
+            return;
+        }
+        let token = if is_kana_run {
+            buffer.as_str().to_romaji()
+        } else {
+            buffer.to_lowercase()
+        };
+        tokens.push(token);
+        buffer.clear();
+    };
+
+    for ch in title.chars() {
+        if is_kana(ch) {
+            flush(&mut word, &mut tokens, false);
+            kana.push(ch);
+            continue;
+        }
+        flush(&mut kana, &mut tokens, true);
+        if let Some(pinyin) = ch.to_pinyin() {
+            flush(&mut word, &mut tokens, false);
+            tokens.push(pinyin.plain().to_string());
+        } else if ch.is_alphanumeric() {
+            word.push(ch);
+        } else {
+            flush(&mut word, &mut tokens, false);
+        }
+    }
+    flush(&mut kana, &mut tokens, true);
+    flush(&mut word, &mut tokens, false);
+    tokens
+}
+
+pub fn title_keys(title: &str) -> TitleKeys {
+    let tokens = romanize(title);
+    let initials = tokens
+        .iter()
+        .filter_map(|token| token.chars().next())
+        .collect::<String>();
+    TitleKeys {
+        sort_key: tokens.join(" "),
+        search_key: format!("{} {}", tokens.concat(), initials),
+    }
+}
+
+pub fn sort_key(title: &str) -> String {
+    title_keys(title).sort_key
+}
+
+/// Fills in keys for rows indexed before they were stored.
+pub fn backfill_keys(conn: &Connection) -> Result<(), String> {
+    let rows = {
+        let mut stmt = conn
+            .prepare(
+                "
+                SELECT id, COALESCE(title, ''), COALESCE(group_name, '')
+                FROM manifest_entries
+                WHERE title_sort_key IS NULL OR group_sort_key IS NULL OR title_search_key IS NULL
+                ",
+            )
+            .map_err(|err| format!("Failed to prepare query: {}", err))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(|err| format!("Failed to read entries: {}", err))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("Failed to parse entry: {}", err))?
+    };
+
+    for (id, title, group) in rows {
+        let keys = title_keys(&title);
+        conn.execute(
+            "
+            UPDATE manifest_entries
+            SET title_sort_key = ?2, group_sort_key = ?3, title_search_key = ?4
+            WHERE id = ?1
+            ",
+            params![id, keys.sort_key, sort_key(&group), keys.search_key],
+        )
+        .map_err(|err| format!("Failed to update sort keys: {}", err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Title, sort key and search key.
+    type Case = (&'static str, &'static str, &'static str);
+
+    #[test]
+    fn titles_are_romanized_by_script() {
+        let cases: &[Case] = &[
+            // Hanzi by pinyin, one syllable each, with initials for short queries.
+            ("葬送的芙莉莲", "zang song de fu li lian", "zangsongdefulilian zsdfll"),
+            // Kana runs by romaji, one token per run; `・` splits words.
+            ("けいおん!", "keion", "keion k"),
+            ("ソードアート・オンライン", "soodoaato onrain", "soodoaatoonrain so"),
+            // Mixed scripts: kanji read as Chinese, kana, Latin words and digits.
+            ("進撃の巨人 Season 2", "jin ji no ju ren season 2", "jinjinojurenseason2 jjnjrs2"),
+            (
+                "Re:ゼロから始める異世界生活",
+                "re zerokara shi meru yi shi jie sheng huo",
+                "rezerokarashimeruyishijieshenghuo rzsmysjsh",
+            ),
+            ("86 -エイティシックス-", "86 eiteishikkusu", "86eiteishikkusu 8e"),
+            ("CLANNAD ～AFTER STORY～", "clannad after story", "clannadafterstory cas"),
+        ];
+        for &(title, sort, search) in cases {
+            let keys = title_keys(title);
+            assert_eq!((keys.sort_key.as_str(), keys.search_key.as_str()), (sort, search), "{}", title);
+        }
+    }
+
+    #[test]
+    fn initials_match_as_a_substring_of_the_search_key() {
+        let keys = title_keys("葬送的芙莉莲");
+        assert!(keys.search_key.contains("zsdfll"));
+        assert!(keys.search_key.contains("fulilian"));
+        assert!(sort_key("阿虚") < sort_key("葬送的芙莉莲"));
+    }
+
+    #[test]
+    fn backfill_fills_missing_keys_only() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&conn).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO manifest_entries (id, library_root, title, group_name)
+            VALUES ('a', '/lib', '葬送的芙莉莲', '2023年10月');
+            INSERT INTO manifest_entries (id, library_root, title, group_name, title_sort_key, group_sort_key, title_search_key)
+            VALUES ('b', '/lib', 'けいおん!', '', 'kept', 'kept', 'kept');
+            INSERT INTO manifest_entries (id, library_root, title, group_name, title_sort_key)
+            VALUES ('c', '/lib', 'Mushishi', NULL, 'stale');
+            ",
+        )
+        .unwrap();
+
+        backfill_keys(&conn).unwrap();
+
+        let keys = |id: &str| {
+            conn.query_row(
+                "SELECT title_sort_key, group_sort_key, title_search_key FROM manifest_entries WHERE id = ?",
+                [id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            keys("a"),
+            (
+                "zang song de fu li lian".to_string(),
+                "2023 nian 10 yue".to_string(),
+                "zangsongdefulilian zsdfll".to_string()
+            )
+        );
+        assert_eq!(keys("b"), ("kept".to_string(), "kept".to_string(), "kept".to_string()));
+        // Any missing key recomputes all three for the row.
+        assert_eq!(
+            keys("c"),
+            ("mushishi".to_string(), String::new(), "mushishi m".to_string())
+        );
+    }
+}
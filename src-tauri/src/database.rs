@@ -0,0 +1,192 @@
+use rusqlite::Connection;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{libraries, migrations};
+
+/// How long a connection waits for another writer before failing with "database is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Prepared statements kept per connection for `prepare_cached`.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+/// Idle connections kept open; more can be checked out at once, the extras are closed on return.
+const MAX_IDLE: usize = 4;
+
+struct Pool {
+    path: PathBuf,
+    /// Bumped when the database moves, so connections to the old file aren't reused.
+    generation: u64,
+    idle: Vec<Connection>,
+    /// Connections handed out and not yet dropped, whether or not they return to `idle`.
+    checked_out: usize,
+}
+
+/// Tauri-managed pool of connections to the index database, shared by commands, the
+/// watcher and the scanner. WAL mode lets readers run while a refresh is writing.
+pub struct Database {
+    pool: Mutex<Pool>,
+}
+
+/// A connection checked out of the pool; it goes back when dropped.
+pub struct PooledConnection<'a> {
+    database: &'a Database,
+    generation: u64,
+    conn: Option<Connection>,
+}
+
+impl Database {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            pool: Mutex::new(Pool {
+                path,
+                generation: 0,
+                idle: Vec::new(),
+                checked_out: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Pool>, String> {
+        self.pool
+            .lock()
+            .map_err(|_| "Database pool state is poisoned".to_string())
+    }
+
+    pub fn path(&self) -> Result<PathBuf, String> {
+        Ok(self.lock()?.path.clone())
+    }
+
+    /// Points the pool at `path` after `move_files` has moved the database there from the
+    /// current path. The pool stays locked meanwhile, so nothing can be checked out, and the
+    /// move is refused while any connection is in use, e.g. by a scan or a watcher refresh
+    /// that would keep writing to the old file.
+    pub fn relocate(
+        &self,
+        path: PathBuf,
+        move_files: impl FnOnce(&Path, &Path) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut pool = self.lock()?;
+        if pool.checked_out > 0 {
+            return Err("数据库正在使用中（可能正在扫描资料库），请稍后再试".to_string());
+        }
+        // Idle connections hold the files open, which blocks moving them on Windows.
+        pool.idle.clear();
+        pool.generation += 1;
+        let current = pool.path.clone();
+        move_files(&current, &path)?;
+        pool.path = path;
+        Ok(())
+    }
+
+    /// Checks out a connection to the shared index database, which holds every library root.
+    pub fn connection(&self) -> Result<PooledConnection<'_>, String> {
+        let (path, generation, idle) = {
+            let mut pool = self.lock()?;
+            pool.checked_out += 1;
+            (pool.path.clone(), pool.generation, pool.idle.pop())
+        };
+        // Counted from here on, so a failed connect gives its slot back on drop.
+        let mut pooled = PooledConnection {
+            database: self,
+            generation,
+            conn: idle,
+        };
+        if pooled.conn.is_none() {
+            pooled.conn = Some(connect(&path)?);
+        }
+        Ok(pooled)
+    }
+
+    /// Checks out a connection for the library rooted at `base_dir`.
+    pub fn open(&self, base_dir: &Path) -> Result<PooledConnection<'_>, String> {
+        if !base_dir.exists() {
+            return Err(format!("Base directory not found: {}", base_dir.display()));
+        }
+        let conn = self.connection()?;
+        libraries::ensure_root(&conn, base_dir)?;
+        Ok(conn)
+    }
+}
+
+fn connect(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|err| format!("Failed to open database: {}", err))?;
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|err| format!("Failed to configure database: {}", err))?;
+    conn.execute_batch(
+        "
+        PRAGMA journal_mode = WAL;
+        PRAGMA synchronous = NORMAL;
+        ",
+    )
+    .map_err(|err| format!("Failed to configure database: {}", err))?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    migrations::migrate(&conn)?;
+    Ok(conn)
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection is present until drop")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection is present until drop")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        let Ok(mut pool) = self.database.pool.lock() else {
+            return;
+        };
+        pool.checked_out -= 1;
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        // A connection left inside a failed transaction must not be handed out again.
+        if conn.is_autocommit() && pool.generation == self.generation && pool.idle.len() < MAX_IDLE {
+            pool.idle.push(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relocate_waits_for_checked_out_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Database::new(dir.path().join("a.sqlite"));
+        let target = dir.path().join("b.sqlite");
+
+        let conn = database.connection().unwrap();
+        assert!(database.relocate(target.clone(), |_, _| Ok(())).is_err());
+        assert_eq!(database.path().unwrap(), dir.path().join("a.sqlite"));
+        drop(conn);
+
+        database
+            .relocate(target.clone(), |from, to| std::fs::rename(from, to).map_err(|err| err.to_string()))
+            .unwrap();
+        assert_eq!(database.path().unwrap(), target);
+        assert!(database.connection().is_ok());
+    }
+
+    #[test]
+    fn failed_move_keeps_the_old_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let current = dir.path().join("a.sqlite");
+        let database = Database::new(current.clone());
+        drop(database.connection().unwrap());
+
+        let result = database.relocate(dir.path().join("b.sqlite"), |_, _| Err("failed".to_string()));
+        assert!(result.is_err());
+        assert_eq!(database.path().unwrap(), current);
+        assert!(database.connection().is_ok());
+    }
+}
@@ -0,0 +1,446 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tauri::AppHandle;
+
+use crate::database::Database;
+use crate::episodes::{is_video_file, parse_episode, EpisodeKind};
+use crate::manifest::{self, ManifestFormat};
+use crate::{history, libraries, new_manifest_document, normalize_path, NewAnimePayload, MANIFEST_FILE_NAME};
+
+static BRACKET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[\[【]([^\[\]【】]*)[\]】]").unwrap());
+/// Decorations such as `★10月新番★` and `(2023)` that are never part of a title.
+static DECORATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"★[^★]*★|\([^()]*\)|（[^（）]*）").unwrap());
+/// Where the title stops in `Title - 05`, `Title S01E05`, `Title S01`, `Title 第05话`,
+/// `Title 全12话`, `Title 01-12` or `Title 1080p WEB-DL`.
+static TITLE_END: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\s-\s*\d|(?:^|\s)s\d{1,2}(?:\s*e\d|\s|$)|全\s*\d{1,4}\s*[话話集]|第\s*[\d零〇一二两三四五六七八九十百]+\s*[话話集回季]|(?:^|\s)(?:ep|episode)\s*\.?\s*\d|\s\d{1,3}(?:\s*[-~～]\s*\d{1,3})?(?:v\d)?(?:\s|$)|(?:^|\s)\d{3,4}[pi](?:\s|$)|(?:^|\s)(?:bd|web|tv|dvd)(?:-?(?:dl|rip))?(?:\s|$)",
+    )
+    .unwrap()
+});
+static RANGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^0-9a-z])(\d{1,3})\s*[-~～]\s*(\d{1,3})(?:[^0-9]|$)").unwrap());
+static EPISODE_TOTAL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"全\s*(\d{1,4})\s*[话話集]").unwrap());
+static FINISHED_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z])(?:fin|end|complete|batch)(?:[^a-z]|$)").unwrap());
+static FINISHED_TEXT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)合集|全集|完结|完結|\d\s*(?:end|fin)(?:[^a-z]|$)").unwrap());
+static RESOLUTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^0-9a-z])(2160|1440|1080|720|576|480)[pi](?:[^a-z]|$)").unwrap());
+static DIMENSIONS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\d{3,4}[x×](\d{3,4})\b").unwrap());
+static UHD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z0-9])(?:4k|uhd)(?:[^a-z]|$)").unwrap());
+static SIMPLIFIED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z])(?:chs|sc|gb|jpsc)(?:[^a-z]|$)|简|簡").unwrap());
+static TRADITIONAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z])(?:cht|tc|big5|jptc)(?:[^a-z]|$)|繁").unwrap());
+static JAPANESE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z])(?:jp|jpn|jpsc|jptc)(?:[^a-z]|$)|日").unwrap());
+static SOFT_SUBS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)内封|內封|(?:^|[^a-z])(?:ass|srt)(?:x\d)?(?:[^a-z]|$)").unwrap());
+static HARD_SUBS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"内嵌|內嵌").unwrap());
+/// Bracket contents that describe the release rather than name it.
+static TAG_WORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^[\d\s.\-~～v]*(?:end|fin)?$|(?:^|[^a-z])(?:bd|bdrip|web|web-?dl|webrip|tv|dvd|hevc|avc|[xh]\.?26[45]|aac|flac|mkv|mp4|10-?bit|batch|ma10p|hi10p)(?:[^a-z]|$)|新番|月番|合集|全集|完结|完結",
+    )
+    .unwrap()
+});
+/// Folder names like `Season 2` or `Disc 1` that say nothing about the title.
+static GENERIC_FOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:season\s*\d+|s\d{1,2}|disc\s*\d+|bdmv|第\s*[\d一二三四五六七八九十]+\s*季)$").unwrap()
+});
+
+/// What a fansub-style release name such as `[Sakurato] Title [01-12][1080p][CHS]` says.
+#[derive(Debug, Default)]
+struct ReleaseName {
+    group: Option<String>,
+    title: Option<String>,
+    quality: Option<String>,
+    subtitle_type: Option<String>,
+    /// Last episode of a batch range like `[01-12]`, or the count in `全12话`.
+    last_episode: Option<i64>,
+    finished: bool,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim_matches(|ch: char| ch.is_whitespace() || matches!(ch, '-' | '_' | '.' | '–' | '|'));
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn quality(text: &str) -> Option<String> {
+    if UHD.is_match(text) {
+        return Some("4K".to_string());
+    }
+    RESOLUTION
+        .captures(text)
+        .or_else(|| DIMENSIONS.captures(text))
+        .map(|captures| format!("{}p", &captures[1]))
+}
+
+/// Reads `CHS`, `BIG5`, `简繁日双语`, `内封` and the like into text such as `简中/繁中 内封`.
+fn subtitle_type(tags: &str) -> Option<String> {
+    let languages = [
+        (&*SIMPLIFIED, "简中"),
+        (&*TRADITIONAL, "繁中"),
+        (&*JAPANESE, "日文"),
+    ]
+    .into_iter()
+    .filter(|(pattern, _)| pattern.is_match(tags))
+    .map(|(_, name)| name)
+    .collect::<Vec<_>>();
+    if languages.is_empty() {
+        return None;
+    }
+    let mut subtitle_type = languages.join("/");
+    if HARD_SUBS.is_match(tags) {
+        subtitle_type.push_str(" 内嵌");
+    } else if SOFT_SUBS.is_match(tags) {
+        subtitle_type.push_str(" 内封");
+    }
+    Some(subtitle_type)
+}
+
+fn is_tag(text: &str) -> bool {
+    TAG_WORD.is_match(text) || quality(text).is_some() || subtitle_type(text).is_some()
+}
+
+fn last_episode(text: &str) -> Option<i64> {
+    let range = RANGE.captures_iter(text).find_map(|captures| {
+        let first = captures[1].parse::<i64>().ok()?;
+        let last = captures[2].parse::<i64>().ok()?;
+        (first < last).then_some(last)
+    });
+    range.or_else(|| EPISODE_TOTAL.captures(text).and_then(|captures| captures[1].parse().ok()))
+}
+
+fn parse_release_name(name: &str) -> ReleaseName {
+    let name = DECORATION.replace_all(name, " ");
+    let name = name.trim();
+
+    let tags = BRACKET
+        .captures_iter(name)
+        .map(|captures| captures[1].trim().to_string())
+        .collect::<Vec<_>>();
+    let leading = BRACKET.find(name).filter(|found| found.start() == 0);
+    let group = leading
+        .and_then(|_| tags.first())
+        .filter(|tag| !is_tag(tag))
+        .cloned();
+    let rest = match (&group, leading) {
+        (Some(_), Some(found)) => &name[found.end()..],
+        _ => name,
+    };
+
+    // Scene-style names separate words with dots or underscores instead of spaces.
+    let mut outside = BRACKET.replace_all(rest, " ").to_string();
+    if !outside.trim().contains(' ') {
+        outside = outside.replace(['.', '_'], " ");
+    }
+    let outside = format!(" {}", outside.trim());
+    let end = TITLE_END.find(&outside).map(|found| found.start()).unwrap_or(outside.len());
+    let title = non_empty(&outside[..end]).or_else(|| {
+        // `[Group][Title][01][1080p]` keeps the title in a bracket of its own.
+        tags.iter()
+            .skip(usize::from(group.is_some()))
+            .find(|tag| !is_tag(tag))
+            .and_then(|tag| non_empty(tag))
+    });
+    // `中文名 / English Name` lists the same title twice.
+    let title = title.and_then(|title| title.split(" / ").next().and_then(non_empty));
+
+    let tag_text = tags.join(" ");
+    ReleaseName {
+        group: group.and_then(|group| non_empty(&group)),
+        title,
+        quality: quality(name),
+        subtitle_type: subtitle_type(&tag_text),
+        last_episode: last_episode(name),
+        finished: FINISHED_TEXT.is_match(name) || tags.iter().any(|tag| FINISHED_TAG.is_match(tag)),
+    }
+}
+
+/// The value most names agree on; ties go to the one seen first.
+fn most_common(values: impl Iterator<Item = Option<String>>) -> Option<String> {
+    let mut counts = Vec::<(String, usize)>::new();
+    for value in values.flatten() {
+        match counts.iter_mut().find(|(seen, _)| *seen == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    let best = counts.iter().map(|(_, count)| *count).max()?;
+    counts.into_iter().find(|(_, count)| *count == best).map(|(value, _)| value)
+}
+
+/// A manifest guessed for a folder that has videos but no manifest, for the user to review.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestProposal {
+    /// The folder the manifest would be written to.
+    path: String,
+    title: String,
+    fansub: String,
+    subtitle_type: String,
+    quality: String,
+    /// Distinct episodes among the videos, or the end of a range like `[01-12]` when larger.
+    episodes: i64,
+    /// Set when the names carry a batch range or a `Fin` / `END` / `全集` marker.
+    is_finished: bool,
+    video_files: usize,
+    /// One of the video file names, to check the guess against.
+    sample_file: String,
+}
+
+fn propose(dir: &Path, file_names: &[String]) -> ManifestProposal {
+    let folder_name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let folder = parse_release_name(&folder_name);
+    let files = file_names
+        .iter()
+        .map(|name| {
+            let stem = Path::new(name).file_stem().map(|stem| stem.to_string_lossy().to_string());
+            parse_release_name(&stem.unwrap_or_else(|| name.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    // Folders named by hand usually carry the title people want; `Season 2` doesn't.
+    let title = folder
+        .title
+        .filter(|_| !GENERIC_FOLDER.is_match(folder_name.trim()))
+        .or_else(|| most_common(files.iter().map(|file| file.title.clone())))
+        .unwrap_or(folder_name);
+
+    let numbers = file_names
+        .iter()
+        .map(|name| parse_episode(name))
+        .filter(|parsed| parsed.kind == EpisodeKind::Episode)
+        .filter_map(|parsed| parsed.number.map(|number| (parsed.season, number.to_bits())))
+        .collect::<BTreeSet<_>>();
+    let last_episode = folder
+        .last_episode
+        .into_iter()
+        .chain(files.iter().filter_map(|file| file.last_episode))
+        .max();
+    let episodes = (numbers.len() as i64).max(last_episode.unwrap_or(0));
+
+    ManifestProposal {
+        path: normalize_path(dir),
+        title,
+        fansub: folder
+            .group
+            .or_else(|| most_common(files.iter().map(|file| file.group.clone())))
+            .unwrap_or_default(),
+        subtitle_type: folder
+            .subtitle_type
+            .or_else(|| most_common(files.iter().map(|file| file.subtitle_type.clone())))
+            .unwrap_or_default(),
+        quality: folder
+            .quality
+            .or_else(|| most_common(files.iter().map(|file| file.quality.clone())))
+            .unwrap_or_default(),
+        episodes,
+        is_finished: last_episode.is_some() || folder.finished || files.iter().any(|file| file.finished),
+        video_files: file_names.len(),
+        sample_file: file_names.first().cloned().unwrap_or_default(),
+    }
+}
+
+/// A parent manifest only groups the entries below it, so their videos stay unmanaged.
+fn is_parent_manifest(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| manifest::parse_manifest(&content, ManifestFormat::from_path(path)).ok())
+        .map(|raw| raw.is_parent)
+        .unwrap_or(false)
+}
+
+/// Finds folders below `base_dir` that hold videos but no manifest, and guesses each one's
+/// manifest from its release names. Nothing is written.
+///
+/// Videos in subfolders of such a folder count towards it, and the library root itself is
+/// never proposed.
+#[tauri::command]
+pub fn discover_manifests(app: AppHandle, base_dir: String) -> Result<Vec<ManifestProposal>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+
+    let settings = libraries::scan_settings(&app, &base);
+    let mut managed = HashSet::<PathBuf>::new();
+    let mut videos = BTreeMap::<PathBuf, Vec<String>>::new();
+    for entry in settings
+        .walk_dir(&base)
+        .into_iter()
+        .filter_entry(|entry| settings.ignore.check(entry.path(), entry.file_type().is_dir()).is_none())
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let path = entry.path();
+        let Some(dir) = path.parent() else {
+            continue;
+        };
+        if settings.manifest_names.is_manifest(path) {
+            if !is_parent_manifest(path) {
+                managed.insert(dir.to_path_buf());
+            }
+        } else if is_video_file(path) {
+            videos
+                .entry(dir.to_path_buf())
+                .or_default()
+                .push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+
+    // Parents sort before their subfolders, so each folder is folded into the topmost
+    // unmanaged folder above it.
+    let mut folders = Vec::<(PathBuf, Vec<String>)>::new();
+    let mut folder_index = HashMap::<PathBuf, usize>::new();
+    for (dir, file_names) in videos {
+        if dir == base || dir.ancestors().any(|ancestor| managed.contains(ancestor)) {
+            continue;
+        }
+        let owner = dir
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| *ancestor != base)
+            .find_map(|ancestor| folder_index.get(ancestor).copied());
+        match owner {
+            Some(index) => folders[index].1.extend(file_names),
+            None => {
+                folder_index.insert(dir.clone(), folders.len());
+                folders.push((dir, file_names));
+            }
+        }
+    }
+
+    Ok(folders
+        .iter()
+        .map(|(dir, file_names)| propose(dir, file_names))
+        .collect())
+}
+
+/// A reviewed proposal: the folder and the manifest fields to write there.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptedManifest {
+    path: String,
+    payload: NewAnimePayload,
+}
+
+/// Writes a `manifest.yml` into each accepted folder and returns the paths written.
+///
+/// Every entry is checked before anything is written, so one bad title doesn't leave the
+/// batch half done. Folders that gained a manifest since they were proposed are skipped.
+#[tauri::command]
+pub fn write_discovered_manifests(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    manifests: Vec<AcceptedManifest>,
+) -> Result<Vec<String>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+
+    // Compared resolved, so `..` or a link can't point a manifest outside the library.
+    let canonical_base =
+        fs::canonicalize(&base).map_err(|err| format!("Failed to resolve {}: {}", base.display(), err))?;
+
+    let mut documents = Vec::new();
+    for accepted in manifests {
+        let dir = PathBuf::from(accepted.path.trim());
+        let inside = fs::canonicalize(&dir)
+            .map(|canonical| canonical.is_dir() && canonical.starts_with(&canonical_base))
+            .unwrap_or(false);
+        if !inside {
+            return Err(format!("Entry directory not found: {}", dir.display()));
+        }
+        let document = new_manifest_document(accepted.payload)
+            .map_err(|err| format!("{}: {}", dir.display(), err))?;
+        documents.push((dir, document));
+    }
+
+    let mut written = Vec::new();
+    for (dir, document) in documents {
+        if libraries::scan_settings(&app, &dir).manifest_names.find(&dir).is_some() {
+            continue;
+        }
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        let content = manifest::render(&document, ManifestFormat::Yaml)?;
+        history::save_manifest(&database, &base, &manifest_path, &content)?;
+        written.push(normalize_path(&manifest_path));
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Name, group, title, quality, subtitle type and last episode.
+    type Case = (&'static str, Option<&'static str>, &'static str, Option<&'static str>, Option<&'static str>, Option<i64>);
+
+    #[test]
+    fn parses_release_names() {
+        let cases: &[Case] = &[
+            (
+                "[Sakurato] Sousou no Frieren [01-12][1080p][CHS]",
+                Some("Sakurato"),
+                "Sousou no Frieren",
+                Some("1080p"),
+                Some("简中"),
+                Some(12),
+            ),
+            ("Spy.x.Family.S01E05.1080p.WEB-DL.x264", None, "Spy x Family", Some("1080p"), None, None),
+            ("Spy.x.Family.S01.2160p.WEB-DL.x265", None, "Spy x Family", Some("2160p"), None, None),
+            (
+                "[桜都字幕组] 葬送的芙莉莲 全12话 [1080p][简繁内封]",
+                Some("桜都字幕组"),
+                "葬送的芙莉莲",
+                Some("1080p"),
+                Some("简中/繁中 内封"),
+                Some(12),
+            ),
+            (
+                "[北宇治字幕组] 葬送的芙莉莲 / Sousou no Frieren [01-28][1080p]",
+                Some("北宇治字幕组"),
+                "葬送的芙莉莲",
+                Some("1080p"),
+                None,
+                Some(28),
+            ),
+            (
+                "【喵萌奶茶屋】★10月新番★[葬送的芙莉莲 / Sousou no Frieren][01][1080p][简日双语]",
+                Some("喵萌奶茶屋"),
+                "葬送的芙莉莲",
+                Some("1080p"),
+                Some("简中/日文"),
+                None,
+            ),
+        ];
+        for (name, group, title, quality, subtitle_type, last_episode) in cases {
+            let parsed = parse_release_name(name);
+            assert_eq!(parsed.group.as_deref(), *group, "{}", name);
+            assert_eq!(parsed.title.as_deref(), Some(*title), "{}", name);
+            assert_eq!(parsed.quality.as_deref(), *quality, "{}", name);
+            assert_eq!(parsed.subtitle_type.as_deref(), *subtitle_type, "{}", name);
+            assert_eq!(parsed.last_episode, *last_episode, "{}", name);
+        }
+    }
+
+    #[test]
+    fn finished_markers() {
+        assert!(parse_release_name("[Group] Title [01-12 Fin][1080p]").finished);
+        assert!(parse_release_name("[Group] Title 全集 [1080p]").finished);
+        assert!(!parse_release_name("[Group] Title [05][1080p]").finished);
+    }
+}
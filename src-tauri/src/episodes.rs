@@ -0,0 +1,371 @@
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::database::Database;
+use crate::normalize_path;
+
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm", "avi", "m4v", "ts", "m2ts", "mov", "wmv", "flv", "rmvb"];
+
+static NOISE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:\d{3,4}p|\d{3,4}x\d{3,4}|[xh]\.?26[45]|hevc|avc|(?:8|10)-?bits?|ma10p|hi10p|yuv4[24]0p?\d*|aac(?:x\d)?|flac(?:x\d)?|ac3|dts|opus|[0-9a-f]{8})\b",
+    )
+    .unwrap()
+});
+static SPECIAL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:^|[^a-z0-9])(ncop|nced|ova|oad|sps?|specials?)[\s_.\-]*(\d{1,3}(?:\.\d)?)?(?:[^a-z]|$)").unwrap()
+});
+static SEASON_EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z0-9])s(\d{1,2})\s*e(\d{1,4}(?:\.\d)?)").unwrap());
+static CJK_EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"第\s*(\d+|[零〇一二两三四五六七八九十百]+)\s*[话話集回]").unwrap());
+static EP_PREFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:^|[^a-z0-9])(?:episode|ep)\s*\.?\s*(\d{1,4}(?:\.\d)?)(?:v\d+)?(?:[^0-9]|$)").unwrap()
+});
+static DASH_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s-\s*(\d{1,4}(?:\.\d)?)(?:v\d+)?(?:[^0-9.]|$)").unwrap());
+static BRACKET_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)[\[【(（](\d{1,3}(?:\.\d)?)(?:v\d+)?(?:\s*end)?[\]】)）]").unwrap());
+static BARE_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[^a-z0-9.])(\d{1,3})(?:v\d+)?(?:[^a-z0-9]|$)").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EpisodeKind {
+    Episode,
+    Special,
+    Ova,
+    Opening,
+    Ending,
+}
+
+impl EpisodeKind {
+    pub fn as_db(self) -> &'static str {
+        match self {
+            EpisodeKind::Episode => "episode",
+            EpisodeKind::Special => "sp",
+            EpisodeKind::Ova => "ova",
+            EpisodeKind::Opening => "ncop",
+            EpisodeKind::Ending => "nced",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "sp" => EpisodeKind::Special,
+            "ova" => EpisodeKind::Ova,
+            "ncop" => EpisodeKind::Opening,
+            "nced" => EpisodeKind::Ending,
+            _ => EpisodeKind::Episode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedEpisode {
+    pub number: Option<f64>,
+    pub season: Option<i64>,
+    pub kind: EpisodeKind,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpisodeFile {
+    path: String,
+    file_name: String,
+    relative_path: String,
+    episode: Option<f64>,
+    season: Option<i64>,
+    kind: EpisodeKind,
+    size: i64,
+    modified_at: i64,
+    watched: bool,
+    watch_count: i64,
+    last_watched_at: i64,
+    position_seconds: Option<f64>,
+}
+
+pub fn is_video_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            VIDEO_EXTENSIONS.contains(&ext.as_str())
+        })
+        .unwrap_or(false)
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok()
+}
+
+fn parse_cjk_number(value: &str) -> Option<f64> {
+    if let Ok(number) = value.parse::<f64>() {
+        return Some(number);
+    }
+    let digit = |ch: char| match ch {
+        '零' | '〇' => Some(0),
+        '一' => Some(1),
+        '二' | '两' => Some(2),
+        '三' => Some(3),
+        '四' => Some(4),
+        '五' => Some(5),
+        '六' => Some(6),
+        '七' => Some(7),
+        '八' => Some(8),
+        '九' => Some(9),
+        _ => None,
+    };
+
+    let mut total = 0;
+    let mut current = 0;
+    for ch in value.chars() {
+        match ch {
+            '百' => {
+                total += current.max(1) * 100;
+                current = 0;
+            }
+            '十' => {
+                total += current.max(1) * 10;
+                current = 0;
+            }
+            _ => current = digit(ch)?,
+        }
+    }
+    Some((total + current) as f64)
+}
+
+/// Extracts the episode number from a release file name.
+///
+/// Understands `Title - 05 [1080p]`, `[05]`, `S01E05`, `第05话`, `EP05` and the
+/// SP/OVA/NCOP/NCED markers used for extras.
+pub fn parse_episode(file_name: &str) -> ParsedEpisode {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|value| value.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_name.to_string());
+    // Full-width digits are common in Japanese releases.
+    let stem = stem
+        .chars()
+        .map(|ch| match ch {
+            '０'..='９' => char::from_u32(ch as u32 - '０' as u32 + '0' as u32).unwrap_or(ch),
+            _ => ch,
+        })
+        .collect::<String>();
+    let cleaned = NOISE.replace_all(&stem, " ");
+
+    if let Some(captures) = SPECIAL.captures(&cleaned) {
+        let kind = match captures[1].to_lowercase().as_str() {
+            "ncop" => EpisodeKind::Opening,
+            "nced" => EpisodeKind::Ending,
+            "ova" | "oad" => EpisodeKind::Ova,
+            _ => EpisodeKind::Special,
+        };
+        return ParsedEpisode {
+            number: captures.get(2).and_then(|value| parse_number(value.as_str())),
+            season: None,
+            kind,
+        };
+    }
+
+    let episode = |number: Option<f64>, season: Option<i64>| ParsedEpisode {
+        number,
+        season,
+        kind: EpisodeKind::Episode,
+    };
+
+    if let Some(captures) = SEASON_EPISODE.captures(&cleaned) {
+        return episode(parse_number(&captures[2]), captures[1].parse::<i64>().ok());
+    }
+    if let Some(captures) = CJK_EPISODE.captures(&cleaned) {
+        return episode(parse_cjk_number(&captures[1]), None);
+    }
+    for pattern in [&*EP_PREFIX, &*DASH_NUMBER, &*BRACKET_NUMBER] {
+        if let Some(captures) = pattern.captures(&cleaned) {
+            return episode(parse_number(&captures[1]), None);
+        }
+    }
+
+    let fallback = BARE_NUMBER
+        .captures_iter(&cleaned)
+        .last()
+        .and_then(|captures| parse_number(&captures[1]));
+    episode(fallback, None)
+}
+
+/// Last indexed state of a video file.
+pub struct EpisodeRecord {
+    pub entry_id: String,
+    pub modified_at: i64,
+    pub size: i64,
+}
+
+/// Returns the id of the closest indexed entry whose folder contains `path`.
+pub fn owning_entry<'a>(
+    base: &Path,
+    path: &Path,
+    entry_dirs: &'a HashMap<String, String>,
+) -> Option<&'a String> {
+    let mut cursor = path.parent();
+    while let Some(dir) = cursor {
+        if let Some(id) = entry_dirs.get(&normalize_path(dir)) {
+            return Some(id);
+        }
+        if dir == base {
+            break;
+        }
+        cursor = dir.parent();
+    }
+    None
+}
+
+pub fn load_episode_records(
+    conn: &Connection,
+    library_root: &str,
+) -> Result<HashMap<String, EpisodeRecord>, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "
+            SELECT path, entry_id, COALESCE(modified_at, 0), COALESCE(size, 0)
+            FROM episode_files
+            WHERE library_root = ?
+            ",
+        )
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+
+    let rows = stmt
+        .query_map([library_root], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                EpisodeRecord {
+                    entry_id: row.get(1)?,
+                    modified_at: row.get(2)?,
+                    size: row.get(3)?,
+                },
+            ))
+        })
+        .map_err(|err| format!("Failed to read episode records: {}", err))?;
+
+    let mut records = HashMap::new();
+    for row in rows {
+        let (path, record) = row.map_err(|err| format!("Failed to parse episode record: {}", err))?;
+        records.insert(path, record);
+    }
+
+    Ok(records)
+}
+
+fn load_episode_files(conn: &Connection, entry_id: &str) -> Result<Vec<EpisodeFile>, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "
+            SELECT
+                e.path,
+                e.file_name,
+                e.relative_path,
+                e.episode_number,
+                e.season,
+                e.kind,
+                COALESCE(e.size, 0),
+                COALESCE(e.modified_at, 0),
+                COALESCE(w.watched, 0),
+                COALESCE(w.watch_count, 0),
+                COALESCE(w.last_watched_at, 0),
+                w.position_seconds
+            FROM episode_files e
+            LEFT JOIN watch_progress w ON w.entry_id = e.entry_id AND w.file_path = e.path
+            WHERE e.entry_id = ?
+            ORDER BY
+                CASE e.kind
+                    WHEN 'episode' THEN 0
+                    WHEN 'sp' THEN 1
+                    WHEN 'ova' THEN 2
+                    WHEN 'ncop' THEN 3
+                    ELSE 4
+                END,
+                COALESCE(e.season, 1),
+                e.episode_number IS NULL,
+                e.episode_number,
+                e.file_name
+            ",
+        )
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+
+    let rows = stmt
+        .query_map([entry_id], |row| {
+            Ok(EpisodeFile {
+                path: row.get(0)?,
+                file_name: row.get(1)?,
+                relative_path: row.get(2)?,
+                episode: row.get(3)?,
+                season: row.get(4)?,
+                kind: EpisodeKind::from_db(&row.get::<_, String>(5)?),
+                size: row.get(6)?,
+                modified_at: row.get(7)?,
+                watched: row.get(8)?,
+                watch_count: row.get(9)?,
+                last_watched_at: row.get(10)?,
+                position_seconds: row.get(11)?,
+            })
+        })
+        .map_err(|err| format!("Failed to read episodes: {}", err))?;
+
+    let mut episodes = Vec::new();
+    for episode in rows {
+        episodes.push(episode.map_err(|err| format!("Failed to parse episode: {}", err))?);
+    }
+
+    Ok(episodes)
+}
+
+#[tauri::command]
+pub fn list_episodes(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+) -> Result<Vec<EpisodeFile>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    load_episode_files(&conn, &entry_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_release_file_names() {
+        let cases: &[(&str, Option<f64>, Option<i64>, EpisodeKind)] = &[
+            ("[LoliHouse] Frieren - 05 [WebRip 1080p HEVC-10bit AAC].mkv", Some(5.0), None, EpisodeKind::Episode),
+            ("[LoliHouse] Title - 05 [1080p].mkv", Some(5.0), None, EpisodeKind::Episode),
+            ("Spy.x.Family.S01E05.1080p.WEB-DL.x264.mkv", Some(5.0), Some(1), EpisodeKind::Episode),
+            ("Title S2E12.mp4", Some(12.0), Some(2), EpisodeKind::Episode),
+            ("[SweetSub] 葬送的芙莉莲 第05话 [1080p].mp4", Some(5.0), None, EpisodeKind::Episode),
+            ("第十二集.mp4", Some(12.0), None, EpisodeKind::Episode),
+            ("Title EP05.mkv", Some(5.0), None, EpisodeKind::Episode),
+            ("Title Ep.05 [720p].mkv", Some(5.0), None, EpisodeKind::Episode),
+            ("[Group] Title - 05v2 [1080p].mkv", Some(5.0), None, EpisodeKind::Episode),
+            ("[Group] Title [05v2][1080p].mkv", Some(5.0), None, EpisodeKind::Episode),
+            ("[Group] Title - 12.5 [1080p].mkv", Some(12.5), None, EpisodeKind::Episode),
+            ("[Group] Title [SP01][1080p].mkv", Some(1.0), None, EpisodeKind::Special),
+            ("[Group] Title OVA [1080p].mkv", None, None, EpisodeKind::Ova),
+            ("[Group] Title OVA2.mkv", Some(2.0), None, EpisodeKind::Ova),
+            ("[Group] Title NCOP1 [1080p].mkv", Some(1.0), None, EpisodeKind::Opening),
+            ("[Group] Title NCED [1080p].mkv", None, None, EpisodeKind::Ending),
+            // A lone "e" is a word (the particle へ), not an episode marker.
+            ("[Group] Kyoushitsu e 2nd Season - 05 [1080p].mkv", Some(5.0), None, EpisodeKind::Episode),
+        ];
+        for (file_name, number, season, kind) in cases {
+            let parsed = parse_episode(file_name);
+            assert_eq!(
+                (parsed.number, parsed.season, parsed.kind),
+                (*number, *season, *kind),
+                "{}",
+                file_name
+            );
+        }
+    }
+}
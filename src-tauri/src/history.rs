@@ -0,0 +1,265 @@
+use chrono::Utc;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::database::Database;
+use crate::{hash_content, normalize_path, MANIFEST_FILE_NAME};
+
+/// Revisions kept per manifest; older ones are pruned on save.
+const HISTORY_LIMIT: i64 = 20;
+
+const TEMP_SUFFIX: &str = ".tmp";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestRevision {
+    id: i64,
+    manifest_path: String,
+    /// For display; a manifest that isn't valid UTF-8 shows replacement characters here,
+    /// while reverting restores its exact bytes.
+    content: String,
+    content_hash: String,
+    saved_at: i64,
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|value| value.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}{}", name, TEMP_SUFFIX))
+}
+
+/// Matches the `.manifest.yml.tmp` files `write_atomic` renames into place.
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            name.starts_with('.') && name.ends_with(TEMP_SUFFIX)
+        })
+        .unwrap_or(false)
+}
+
+/// Writes through a temp file in the same folder, flushed to disk before it replaces
+/// `path`, so a crash or full disk leaves either the old file or the new one.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let temp = temp_path(path);
+    let result = (|| -> std::io::Result<()> {
+        let mut file = File::create(&temp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp, path)
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Failed to write {}: {}", path.display(), err));
+    }
+
+    // Persist the rename itself; directories can't be opened for syncing on Windows.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Reads revision content, stored as text by earlier versions and as a blob since, so
+/// manifests in other encodings are kept byte for byte.
+fn content_bytes(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Vec<u8>> {
+    match row.get_ref(index)? {
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => Ok(bytes.to_vec()),
+        other => Err(rusqlite::Error::InvalidColumnType(index, "content".to_string(), other.data_type())),
+    }
+}
+
+fn record_revision(conn: &Connection, library_root: &str, manifest_path: &str, content: &[u8]) -> Result<(), String> {
+    let content_hash = hash_content(content);
+    let latest = conn
+        .query_row(
+            "
+            SELECT content_hash FROM manifest_history
+            WHERE manifest_path = ?1
+            ORDER BY id DESC
+            LIMIT 1
+            ",
+            [manifest_path],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read manifest history: {}", err))?;
+    if latest.as_deref() == Some(content_hash.as_str()) {
+        return Ok(());
+    }
+
+    conn.execute(
+        "
+        INSERT INTO manifest_history (library_root, manifest_path, content, content_hash, saved_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ",
+        params![library_root, manifest_path, content, content_hash, Utc::now().timestamp()],
+    )
+    .map_err(|err| format!("Failed to save manifest history: {}", err))?;
+    conn.execute(
+        "
+        DELETE FROM manifest_history
+        WHERE manifest_path = ?1 AND id NOT IN (
+            SELECT id FROM manifest_history
+            WHERE manifest_path = ?1
+            ORDER BY id DESC
+            LIMIT ?2
+        )
+        ",
+        params![manifest_path, HISTORY_LIMIT],
+    )
+    .map_err(|err| format!("Failed to prune manifest history: {}", err))?;
+    Ok(())
+}
+
+/// Atomically replaces a manifest, first saving the version it replaces to the history
+/// kept in the library database under `base`.
+pub fn save_manifest(database: &Database, base: &Path, manifest_path: &Path, content: &str) -> Result<(), String> {
+    save_manifest_bytes(database, base, manifest_path, content.as_bytes())
+}
+
+fn save_manifest_bytes(database: &Database, base: &Path, manifest_path: &Path, content: &[u8]) -> Result<(), String> {
+    if let Ok(previous) = fs::read(manifest_path) {
+        let conn = database.open(base)?;
+        record_revision(&conn, &normalize_path(base), &normalize_path(manifest_path), &previous)?;
+    }
+    write_atomic(manifest_path, content)
+}
+
+/// Deletes a manifest, keeping its last content in the history.
+pub fn remove_manifest(database: &Database, base: &Path, manifest_path: &Path) -> Result<(), String> {
+    let previous =
+        fs::read(manifest_path).map_err(|err| format!("Failed to read {}: {}", manifest_path.display(), err))?;
+    let conn = database.open(base)?;
+    record_revision(&conn, &normalize_path(base), &normalize_path(manifest_path), &previous)?;
+    fs::remove_file(manifest_path)
+        .map_err(|err| format!("Failed to remove {}: {}", manifest_path.display(), err))
+}
+
+/// The manifest the entry was indexed from, which may be `manifest.yaml` or another
+/// accepted name; entries without one yet get a `manifest.yml` path.
+fn entry_manifest_path(conn: &Connection, entry_id: &str) -> Result<String, String> {
+    let indexed = conn
+        .query_row("SELECT path FROM manifest_files WHERE entry_id = ?1", [entry_id], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+        .map_err(|err| format!("Failed to read manifest record: {}", err))?;
+    if let Some(path) = indexed {
+        return Ok(path);
+    }
+    let entry_dir = conn
+        .query_row("SELECT path FROM manifest_entries WHERE id = ?1", [entry_id], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+        .map_err(|err| format!("Failed to read entry: {}", err))?
+        .ok_or_else(|| format!("Entry not found: {}", entry_id))?;
+    Ok(normalize_path(&Path::new(&entry_dir).join(MANIFEST_FILE_NAME)))
+}
+
+/// Lists saved revisions of an entry's manifest, newest first.
+#[tauri::command]
+pub fn list_manifest_history(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+) -> Result<Vec<ManifestRevision>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    let manifest_path = entry_manifest_path(&conn, &entry_id)?;
+
+    let mut stmt = conn
+        .prepare(
+            "
+            SELECT id, manifest_path, content, content_hash, saved_at
+            FROM manifest_history
+            WHERE manifest_path = ?
+            ORDER BY id DESC
+            ",
+        )
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+    let rows = stmt
+        .query_map([manifest_path], |row| {
+            Ok(ManifestRevision {
+                id: row.get(0)?,
+                manifest_path: row.get(1)?,
+                content: String::from_utf8_lossy(&content_bytes(row, 2)?).into_owned(),
+                content_hash: row.get(3)?,
+                saved_at: row.get(4)?,
+            })
+        })
+        .map_err(|err| format!("Failed to read manifest history: {}", err))?;
+
+    let mut revisions = Vec::new();
+    for revision in rows {
+        revisions.push(revision.map_err(|err| format!("Failed to parse manifest history: {}", err))?);
+    }
+    Ok(revisions)
+}
+
+/// Restores a saved revision of an entry's manifest. The content it replaces is saved
+/// first, so a revert can itself be reverted.
+#[tauri::command]
+pub fn revert_manifest(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+    revision_id: i64,
+) -> Result<String, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    let manifest_path = entry_manifest_path(&conn, &entry_id)?;
+    let content = conn
+        .query_row(
+            "SELECT content FROM manifest_history WHERE id = ?1 AND manifest_path = ?2",
+            params![revision_id, manifest_path],
+            |row| content_bytes(row, 0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read manifest history: {}", err))?
+        .ok_or_else(|| format!("Revision {} not found for {}", revision_id, manifest_path))?;
+    drop(conn);
+
+    save_manifest_bytes(&database, &base, Path::new(&manifest_path), &content)?;
+    Ok(manifest_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revisions_keep_non_utf8_bytes() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&conn).unwrap();
+        // "标题" in GBK, which is not valid UTF-8.
+        let content = b"title: \xb1\xea\xcc\xe2\n".to_vec();
+        record_revision(&conn, "/library", "/library/a/manifest.yml", &content).unwrap();
+        conn.execute(
+            "INSERT INTO manifest_history (library_root, manifest_path, content, content_hash, saved_at)
+             VALUES ('/library', '/library/b/manifest.yml', 'title: text', '', 0)",
+            [],
+        )
+        .unwrap();
+
+        let read = |path: &str| {
+            conn.query_row("SELECT content FROM manifest_history WHERE manifest_path = ?1", [path], |row| {
+                content_bytes(row, 0)
+            })
+            .unwrap()
+        };
+        assert_eq!(read("/library/a/manifest.yml"), content);
+        assert_eq!(read("/library/b/manifest.yml"), b"title: text");
+    }
+}
@@ -0,0 +1,350 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{hash_content, normalize_path, MANIFEST_FILE_NAME};
+
+/// An entry whose folder was renamed or moved, re-linked from its old id to its new one.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMove {
+    pub from: String,
+    pub to: String,
+}
+
+/// One side of a possible move: an entry that disappeared or one that appeared.
+pub struct Candidate<'a> {
+    pub id: &'a str,
+    pub path: &'a str,
+}
+
+/// Looks up the folder of each entry in `ids`.
+pub fn candidates<'a>(ids: &'a [String], paths: &'a HashMap<String, String>) -> Vec<Candidate<'a>> {
+    ids.iter()
+        .filter_map(|id| {
+            paths.get(id).map(|path| Candidate {
+                id: id.as_str(),
+                path: path.as_str(),
+            })
+        })
+        .collect()
+}
+
+struct Identity {
+    uid: String,
+    fingerprint: String,
+}
+
+fn load_identity(conn: &Connection, id: &str) -> Result<Identity, String> {
+    conn.query_row(
+        "SELECT COALESCE(uid, ''), COALESCE(fingerprint, '') FROM manifest_entries WHERE id = ?1",
+        [id],
+        |row| {
+            Ok(Identity {
+                uid: row.get(0)?,
+                fingerprint: row.get(1)?,
+            })
+        },
+    )
+    .map_err(|err| format!("Failed to read entry identity: {}", err))
+}
+
+/// Hashes the names and sizes of an entry's video files, which stay the same when only
+/// the folder around them is renamed or moved. Empty for an entry without videos.
+fn media_fingerprint(conn: &Connection, entry_id: &str) -> Result<String, String> {
+    let mut stmt = conn
+        .prepare_cached("SELECT file_name, size FROM episode_files WHERE entry_id = ?1 ORDER BY file_name, size")
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+    let files = stmt
+        .query_map([entry_id], |row| {
+            Ok(format!(
+                "{}\t{}",
+                row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                row.get::<_, Option<i64>>(1)?.unwrap_or(0)
+            ))
+        })
+        .map_err(|err| format!("Failed to read episodes: {}", err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Failed to parse episode: {}", err))?;
+    if files.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(hash_content(files.join("\n").as_bytes()))
+}
+
+/// Recomputes the stored fingerprint of entries whose video files changed.
+pub fn update_fingerprints<'a>(conn: &Connection, entry_ids: impl IntoIterator<Item = &'a String>) -> Result<(), String> {
+    for id in entry_ids {
+        let fingerprint = media_fingerprint(conn, id)?;
+        conn.execute(
+            "UPDATE manifest_entries SET fingerprint = ?2 WHERE id = ?1 AND COALESCE(fingerprint, '') <> ?2",
+            params![id, fingerprint],
+        )
+        .map_err(|err| format!("Failed to update fingerprint: {}", err))?;
+    }
+    Ok(())
+}
+
+/// Pairs removed entries with added ones that are the same title in a new place: first by
+/// the `id` written in the manifest, then by media fingerprint when exactly one entry on
+/// each side has it.
+fn match_moves(removed: &[Identity], added: &[Identity]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut used_removed = vec![false; removed.len()];
+    let mut used_added = vec![false; added.len()];
+
+    for (r, old) in removed.iter().enumerate() {
+        if old.uid.is_empty() {
+            continue;
+        }
+        if let Some(a) = (0..added.len()).find(|&a| !used_added[a] && added[a].uid == old.uid) {
+            used_removed[r] = true;
+            used_added[a] = true;
+            pairs.push((r, a));
+        }
+    }
+
+    let unmatched = |items: &[Identity], used: &[bool], fingerprint: &str| {
+        (0..items.len())
+            .filter(|&index| !used[index] && items[index].fingerprint == fingerprint)
+            .collect::<Vec<usize>>()
+    };
+    for r in 0..removed.len() {
+        let fingerprint = removed[r].fingerprint.as_str();
+        if used_removed[r] || fingerprint.is_empty() {
+            continue;
+        }
+        if let ([_], [a]) = (
+            unmatched(removed, &used_removed, fingerprint).as_slice(),
+            unmatched(added, &used_added, fingerprint).as_slice(),
+        ) {
+            used_removed[r] = true;
+            used_added[*a] = true;
+            pairs.push((r, *a));
+        }
+    }
+    pairs
+}
+
+/// Rewrites `column` from under `from` to under `to` for rows selected by `filter`.
+fn move_paths(conn: &Connection, table: &str, column: &str, filter: &str, key: &str, from: &str, to: &str) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "UPDATE OR REPLACE {table}
+             SET {column} = CASE
+                 WHEN substr({column}, 1, length(?2)) = ?2 THEN ?3 || substr({column}, length(?2) + 1)
+                 ELSE {column}
+             END
+             WHERE {filter} = ?1",
+        ),
+        params![key, from, to],
+    )
+    .map_err(|err| format!("Failed to update {}: {}", table, err))?;
+    Ok(())
+}
+
+/// Points the history of the manifest that sat directly in `from`'s folder, whatever its
+/// name, at the manifest now indexed for `to`.
+fn move_manifest_history(conn: &Connection, from: &Candidate, to: &Candidate) -> Result<(), String> {
+    let prefix = normalize_path(&Path::new(from.path).join(""));
+    let old_paths = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT DISTINCT manifest_path FROM manifest_history
+                 WHERE substr(manifest_path, 1, length(?1)) = ?1",
+            )
+            .map_err(|err| format!("Failed to read manifest history: {}", err))?;
+        let rows = stmt
+            .query_map([prefix.as_str()], |row| row.get::<_, String>(0))
+            .map_err(|err| format!("Failed to read manifest history: {}", err))?;
+        rows.filter_map(Result::ok)
+            .filter(|path| !path[prefix.len()..].contains(['/', '\\']))
+            .collect::<Vec<_>>()
+    };
+    if old_paths.is_empty() {
+        return Ok(());
+    }
+
+    let new_path = conn
+        .query_row("SELECT path FROM manifest_files WHERE entry_id = ?1", [to.id], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+        .map_err(|err| format!("Failed to read manifest record: {}", err))?
+        .unwrap_or_else(|| normalize_path(&Path::new(to.path).join(MANIFEST_FILE_NAME)));
+    for old_path in old_paths {
+        conn.execute(
+            "UPDATE manifest_history SET manifest_path = ?2 WHERE manifest_path = ?1",
+            params![old_path, new_path],
+        )
+        .map_err(|err| format!("Failed to update manifest history: {}", err))?;
+    }
+    Ok(())
+}
+
+/// Merges `from`'s play history and watch progress into `to`, which may already have
+/// its own from being played since it appeared. Where both have a row, the more recent
+/// one wins and watch counts add up.
+fn merge_progress(conn: &Connection, from: &str, to: &str) -> Result<(), String> {
+    conn.execute(
+        "
+        INSERT INTO play_history (entry_id, last_played_path, last_played_name, updated_at)
+        SELECT ?2, last_played_path, last_played_name, updated_at FROM play_history WHERE entry_id = ?1
+        ON CONFLICT(entry_id) DO UPDATE SET
+            last_played_path = excluded.last_played_path,
+            last_played_name = excluded.last_played_name,
+            updated_at = excluded.updated_at
+        WHERE COALESCE(excluded.updated_at, 0) > COALESCE(play_history.updated_at, 0)
+        ",
+        params![from, to],
+    )
+    .map_err(|err| format!("Failed to update play_history: {}", err))?;
+    conn.execute(
+        "
+        INSERT INTO watch_progress (
+            entry_id,
+            file_path,
+            watched,
+            watch_count,
+            first_watched_at,
+            last_watched_at,
+            position_seconds,
+            updated_at
+        )
+        SELECT ?2, file_path, watched, watch_count, first_watched_at, last_watched_at, position_seconds, updated_at
+        FROM watch_progress WHERE entry_id = ?1
+        ON CONFLICT(entry_id, file_path) DO UPDATE SET
+            watched = MAX(watched, excluded.watched),
+            watch_count = watch_count + excluded.watch_count,
+            first_watched_at = COALESCE(
+                MIN(first_watched_at, excluded.first_watched_at),
+                first_watched_at,
+                excluded.first_watched_at
+            ),
+            last_watched_at = COALESCE(
+                MAX(last_watched_at, excluded.last_watched_at),
+                last_watched_at,
+                excluded.last_watched_at
+            ),
+            position_seconds = CASE
+                WHEN COALESCE(excluded.updated_at, 0) > COALESCE(updated_at, 0) THEN excluded.position_seconds
+                ELSE position_seconds
+            END,
+            updated_at = COALESCE(MAX(updated_at, excluded.updated_at), updated_at, excluded.updated_at)
+        ",
+        params![from, to],
+    )
+    .map_err(|err| format!("Failed to update watch_progress: {}", err))?;
+    for table in ["play_history", "watch_progress"] {
+        conn.execute(&format!("DELETE FROM {} WHERE entry_id = ?1", table), [from])
+            .map_err(|err| format!("Failed to update {}: {}", table, err))?;
+    }
+    Ok(())
+}
+
+fn relink(conn: &Connection, from: &Candidate, to: &Candidate) -> Result<(), String> {
+    // Paths move first, so progress on a file lines up with `to`'s row for the same file.
+    move_paths(conn, "play_history", "last_played_path", "entry_id", from.id, from.path, to.path)?;
+    move_paths(conn, "watch_progress", "file_path", "entry_id", from.id, from.path, to.path)?;
+    merge_progress(conn, from.id, to.id)?;
+    move_manifest_history(conn, from, to)?;
+    conn.execute(
+        "
+        UPDATE manifest_entries
+        SET added_at = (SELECT added_at FROM manifest_entries WHERE id = ?1)
+        WHERE id = ?2 AND EXISTS (SELECT 1 FROM manifest_entries WHERE id = ?1 AND added_at IS NOT NULL)
+        ",
+        params![from.id, to.id],
+    )
+    .map_err(|err| format!("Failed to update entry: {}", err))?;
+    Ok(())
+}
+
+/// Finds entries among `removed` that reappeared among `added` and moves their play
+/// history, watch progress, manifest history and added date over to the new id.
+///
+/// Runs after the added entries and their episodes are written and before the removed
+/// entries are deleted, so both sides can still be read.
+pub fn reconcile(conn: &Connection, removed: &[Candidate], added: &[Candidate]) -> Result<Vec<EntryMove>, String> {
+    if removed.is_empty() || added.is_empty() {
+        return Ok(Vec::new());
+    }
+    let load = |items: &[Candidate]| -> Result<Vec<Identity>, String> {
+        items.iter().map(|item| load_identity(conn, item.id)).collect()
+    };
+    let removed_identities = load(removed)?;
+    let added_identities = load(added)?;
+
+    let mut moves = Vec::new();
+    for (r, a) in match_moves(&removed_identities, &added_identities) {
+        let (from, to) = (&removed[r], &added[a]);
+        relink(conn, from, to)?;
+        moves.push(EntryMove {
+            from: from.id.to_string(),
+            to: to.id.to_string(),
+        });
+    }
+    Ok(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relink_merges_progress_into_existing_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&conn).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO play_history VALUES ('old', '/lib/A/01.mkv', '01.mkv', 200);
+            INSERT INTO play_history VALUES ('new', '/lib/B/01.mkv', '01.mkv', 100);
+            INSERT INTO watch_progress VALUES ('old', '/lib/A/01.mkv', 1, 2, 10, 200, NULL, 200);
+            INSERT INTO watch_progress VALUES ('old', '/lib/A/02.mkv', 0, 0, NULL, NULL, 42.0, 150);
+            INSERT INTO watch_progress VALUES ('new', '/lib/B/01.mkv', 0, 1, 50, 100, 12.0, 100);
+            ",
+        )
+        .unwrap();
+
+        let from = Candidate { id: "old", path: "/lib/A" };
+        let to = Candidate { id: "new", path: "/lib/B" };
+        relink(&conn, &from, &to).unwrap();
+
+        let play = conn
+            .query_row("SELECT entry_id, last_played_path, updated_at FROM play_history", [], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+            })
+            .unwrap();
+        assert_eq!(play, ("new".to_string(), "/lib/B/01.mkv".to_string(), 200));
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT entry_id, file_path, watched, watch_count, first_watched_at, last_watched_at, position_seconds
+                 FROM watch_progress ORDER BY file_path",
+            )
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                    row.get::<_, Option<f64>>(6)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("new".to_string(), "/lib/B/01.mkv".to_string(), true, 3, Some(10), Some(200), None),
+                ("new".to_string(), "/lib/B/02.mkv".to_string(), false, 0, None, None, Some(42.0)),
+            ]
+        );
+    }
+}
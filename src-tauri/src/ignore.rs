@@ -0,0 +1,417 @@
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+
+use crate::{libraries, normalize_path};
+
+/// Per-directory ignore file, in gitignore syntax, relative to the folder it sits in.
+pub const IGNORE_FILE_NAME: &str = ".animeignore";
+
+/// Applied before the library's own patterns; a `!` pattern there re-includes any of them.
+const DEFAULT_PATTERNS: &[&str] = &[
+    "$RECYCLE.BIN/",
+    "System Volume Information/",
+    ".Trash-*/",
+    ".Trashes/",
+    "#recycle/",
+    "@eaDir/",
+    "@Recycle/",
+    ".git/",
+    "*.part",
+    "*.!qB",
+    "*.!ut",
+    "*.crdownload",
+    "*.aria2",
+];
+
+/// A path the scanner left out, with the pattern that matched it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedPath {
+    path: String,
+    pattern: String,
+    /// The `.animeignore` file the pattern is from, or `default` / `library` for the
+    /// built-in patterns and the ones set on the library.
+    source: String,
+    line: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum RuleSource {
+    Default,
+    Library,
+    File(PathBuf, usize),
+}
+
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+    pattern: String,
+    source: RuleSource,
+}
+
+/// Translates one gitignore glob to a regex over `/`-separated paths relative to the
+/// folder the pattern belongs to.
+fn glob_to_regex(glob: &str, anchored: bool) -> String {
+    let chars = glob.chars().collect::<Vec<char>>();
+    let mut regex = String::from("^");
+    if !anchored {
+        regex.push_str("(?:.*/)?");
+    }
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            '*' if chars.get(index + 1) == Some(&'*') => {
+                let at_segment_start = index == 0 || chars[index - 1] == '/';
+                if at_segment_start && chars.get(index + 2) == Some(&'/') {
+                    regex.push_str("(?:.*/)?");
+                    index += 3;
+                } else {
+                    regex.push_str(".*");
+                    index += 2;
+                }
+            }
+            '*' => {
+                regex.push_str("[^/]*");
+                index += 1;
+            }
+            '?' => {
+                regex.push_str("[^/]");
+                index += 1;
+            }
+            '[' => {
+                // A `]` right after the opening bracket (or its negation) is a literal.
+                let start = index + 1;
+                let first = if matches!(chars.get(start), Some('!' | '^')) { start + 1 } else { start };
+                let close = (first + 1..chars.len()).find(|&at| chars[at] == ']');
+                let Some(close) = close.filter(|_| first < chars.len()) else {
+                    regex.push_str(r"\[");
+                    index += 1;
+                    continue;
+                };
+                regex.push('[');
+                if first > start {
+                    regex.push('^');
+                }
+                for &c in &chars[first..close] {
+                    if matches!(c, '\\' | '[' | ']' | '^' | '&' | '~') {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+                index = close + 1;
+            }
+            '\\' if index + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[index + 1].to_string()));
+                index += 2;
+            }
+            c => {
+                regex.push_str(&regex::escape(&c.to_string()));
+                index += 1;
+            }
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn parse_rule(line: &str, source: RuleSource) -> Option<Rule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, glob) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, glob) = match glob.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, glob),
+    };
+    // A slash anywhere but the end ties the pattern to the folder it was written for.
+    let anchored = glob.contains('/');
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    if glob.is_empty() {
+        return None;
+    }
+    // Windows and most NAS shares don't tell names apart by case.
+    let regex = RegexBuilder::new(&glob_to_regex(glob, anchored))
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+    Some(Rule {
+        regex,
+        negated,
+        dir_only,
+        pattern: line.to_string(),
+        source,
+    })
+}
+
+fn read_ignore_file(dir: &Path) -> Vec<Rule> {
+    let path = dir.join(IGNORE_FILE_NAME);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| parse_rule(line, RuleSource::File(path.clone(), index + 1)))
+        .collect()
+}
+
+/// `path` relative to `dir`, `/`-separated on every platform as the patterns expect.
+fn relative(path: &Path, dir: &Path) -> String {
+    path.strip_prefix(dir)
+        .map(|rest| {
+            rest.components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .unwrap_or_default()
+}
+
+/// The ignore patterns in effect for one library: built-in, library-level, then every
+/// `.animeignore` between the root and the path, with later matches winning as in git.
+pub struct IgnoreRules {
+    base: PathBuf,
+    root_rules: Vec<Rule>,
+    /// Parsed `.animeignore` files by folder, read the first time a path below it is checked.
+    files: Mutex<HashMap<PathBuf, Arc<Vec<Rule>>>>,
+}
+
+impl IgnoreRules {
+    pub fn new(base: &Path, patterns: &[String]) -> Self {
+        let root_rules = DEFAULT_PATTERNS
+            .iter()
+            .filter_map(|pattern| parse_rule(pattern, RuleSource::Default))
+            .chain(patterns.iter().filter_map(|pattern| parse_rule(pattern, RuleSource::Library)))
+            .collect();
+        Self {
+            base: base.to_path_buf(),
+            root_rules,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn file_rules(&self, dir: &Path) -> Arc<Vec<Rule>> {
+        if let Ok(files) = self.files.lock() {
+            if let Some(rules) = files.get(dir) {
+                return rules.clone();
+            }
+        }
+        let rules = Arc::new(read_ignore_file(dir));
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(dir.to_path_buf(), rules.clone());
+        }
+        rules
+    }
+
+    /// Returns why `path` is skipped, or `None` when it is scanned. Only the path itself is
+    /// checked: walkers never enter an ignored folder, so its parents are known to be kept.
+    pub fn check(&self, path: &Path, is_dir: bool) -> Option<SkippedPath> {
+        let Ok(rest) = path.strip_prefix(&self.base) else {
+            return None;
+        };
+        if rest.as_os_str().is_empty() {
+            return None;
+        }
+
+        // Later rules, and rules from deeper folders, override earlier ones.
+        let mut verdict = None::<(bool, String, RuleSource)>;
+        let mut consider = |rules: &[Rule], dir: &Path| {
+            let relative = relative(path, dir);
+            if let Some(rule) = rules
+                .iter()
+                .rev()
+                .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&relative))
+            {
+                verdict = Some((rule.negated, rule.pattern.clone(), rule.source.clone()));
+            }
+        };
+
+        consider(&self.root_rules, &self.base);
+        let mut dir = self.base.clone();
+        consider(&self.file_rules(&dir), &dir);
+        let components = rest.components().collect::<Vec<_>>();
+        for component in &components[..components.len() - 1] {
+            dir.push(component);
+            consider(&self.file_rules(&dir), &dir);
+        }
+
+        let (negated, pattern, source) = verdict?;
+        if negated {
+            return None;
+        }
+        let (source, line) = match source {
+            RuleSource::Default => ("default".to_string(), None),
+            RuleSource::Library => ("library".to_string(), None),
+            RuleSource::File(file, line) => (normalize_path(&file), Some(line)),
+        };
+        Some(SkippedPath {
+            path: normalize_path(path),
+            pattern,
+            source,
+            line,
+        })
+    }
+
+    /// Like `check`, but also looks at every folder between the root and `path`, for
+    /// explaining why something deep inside an ignored folder isn't indexed.
+    pub fn explain(&self, path: &Path) -> Option<SkippedPath> {
+        let rest = path.strip_prefix(&self.base).ok()?;
+        let mut current = self.base.clone();
+        for component in rest.components() {
+            current.push(component);
+            if let Some(skipped) = self.check(&current, current.is_dir()) {
+                return Some(skipped);
+            }
+        }
+        None
+    }
+}
+
+/// Explains why `path` is left out of its library, or returns `None` when it is scanned.
+#[tauri::command]
+pub fn explain_ignored(app: AppHandle, path: String) -> Result<Option<SkippedPath>, String> {
+    let path = PathBuf::from(path.trim());
+    if !path.exists() {
+        return Err(format!("Path not found: {}", path.display()));
+    }
+    Ok(libraries::scan_settings(&app, &path).ignore.explain(&path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pattern, path relative to the pattern's folder, whether it is a folder, and whether
+    /// the pattern matches it.
+    type Case = (&'static str, &'static str, bool, bool);
+
+    fn matches(pattern: &str, path: &str, is_dir: bool) -> bool {
+        let rule = parse_rule(pattern, RuleSource::Library).unwrap();
+        (is_dir || !rule.dir_only) && rule.regex.is_match(path)
+    }
+
+    #[test]
+    fn globs_match_like_gitignore() {
+        let cases: &[Case] = &[
+            // `**` at the start, in the middle and at the end.
+            ("**/cache", "cache", true, true),
+            ("**/cache", "Show/S1/cache", true, true),
+            ("**/cache", "Show/cached", true, false),
+            ("a/**/b", "a/b", false, true),
+            ("a/**/b", "a/x/y/b", false, true),
+            ("a/**/b", "ab/b", false, false),
+            ("Extras/**", "Extras/x/y.mkv", false, true),
+            ("Extras/**", "Extras", true, false),
+            ("Extras/**", "Show/Extras/x.mkv", false, false),
+            // A leading `/` (or any inner slash) anchors to the pattern's folder.
+            ("/Extras", "Extras", true, true),
+            ("/Extras", "Show/Extras", true, false),
+            ("Show/Extras", "Show/Extras", true, true),
+            ("Show/Extras", "Other/Show/Extras", true, false),
+            ("Extras", "Show/Extras", true, true),
+            // A trailing `/` only matches folders.
+            ("Samples/", "Samples", true, true),
+            ("Samples/", "Samples", false, false),
+            ("Samples/", "Show/Samples", true, true),
+            // Wildcards stay within one path segment.
+            ("*.part", "Show/ep1.mkv.part", false, true),
+            ("*.part", "ep1.partial", false, false),
+            ("?.txt", "a.txt", false, true),
+            ("?.txt", "ab.txt", false, false),
+            ("Show*", "Show/x", true, false),
+            // Escapes and character classes.
+            (r"\#notes", "#notes", false, true),
+            (r"\!important", "!important", false, true),
+            (r"\*", "*", false, true),
+            (r"\*", "a", false, false),
+            ("ep[0-9].mkv", "ep1.mkv", false, true),
+            ("ep[0-9].mkv", "epa.mkv", false, false),
+            ("ep[!0-9].mkv", "epa.mkv", false, true),
+            ("ep[^0-9].mkv", "ep1.mkv", false, false),
+            ("[]a].txt", "].txt", false, true),
+            ("[ab", "[ab", false, true),
+            // Names are compared without regard to case.
+            ("*.MKV", "ep1.mkv", false, true),
+        ];
+        for &(pattern, path, is_dir, expected) in cases {
+            assert_eq!(matches(pattern, path, is_dir), expected, "{} against {}", pattern, path);
+        }
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_not_rules() {
+        assert!(parse_rule("# note", RuleSource::Library).is_none());
+        assert!(parse_rule("   ", RuleSource::Library).is_none());
+        assert!(parse_rule("/", RuleSource::Library).is_none());
+    }
+
+    fn rules(base: &Path, patterns: &[&str]) -> IgnoreRules {
+        let patterns = patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>();
+        IgnoreRules::new(base, &patterns)
+    }
+
+    #[test]
+    fn later_patterns_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+
+        let keep_last = rules(base, &["*.nfo", "!keep.nfo"]);
+        assert!(keep_last.check(&base.join("a.nfo"), false).is_some());
+        assert!(keep_last.check(&base.join("keep.nfo"), false).is_none());
+
+        let skip_last = rules(base, &["!keep.nfo", "*.nfo"]);
+        assert!(skip_last.check(&base.join("keep.nfo"), false).is_some());
+    }
+
+    #[test]
+    fn built_in_library_and_folder_rules_combine() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("Show")).unwrap();
+        fs::write(base.join(IGNORE_FILE_NAME), "# 花絮\nExtras/\n").unwrap();
+        fs::write(base.join("Show").join(IGNORE_FILE_NAME), "!Extras/\n").unwrap();
+        let rules = rules(base, &["!*.part", "*.nfo"]);
+
+        // Built-in patterns apply unless the library re-includes them.
+        let recycled = rules.check(&base.join("$RECYCLE.BIN"), true).unwrap();
+        assert_eq!((recycled.source.as_str(), recycled.line), ("default", None));
+        assert!(rules.check(&base.join("ep1.mkv.part"), false).is_none());
+
+        let nfo = rules.check(&base.join("Show").join("tvshow.nfo"), false).unwrap();
+        assert_eq!((nfo.pattern.as_str(), nfo.source.as_str()), ("*.nfo", "library"));
+
+        // A root `.animeignore` rule, undone by a deeper one.
+        let extras = rules.check(&base.join("Other").join("Extras"), true).unwrap();
+        assert_eq!(extras.source, normalize_path(&base.join(IGNORE_FILE_NAME)));
+        assert_eq!(extras.line, Some(2));
+        assert!(rules.check(&base.join("Show").join("Extras"), true).is_none());
+        assert!(rules.check(&base.join("Other").join("Extras"), false).is_none());
+    }
+
+    #[test]
+    fn explain_reports_the_ignored_folder_above_a_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        let deep = base.join("Show").join("Samples").join("clip").join("a.mkv");
+        fs::create_dir_all(deep.parent().unwrap()).unwrap();
+        fs::write(&deep, "").unwrap();
+        let rules = rules(base, &["Samples/"]);
+
+        assert!(rules.check(&deep, false).is_none());
+        let skipped = rules.explain(&deep).unwrap();
+        assert_eq!(skipped.path, normalize_path(&base.join("Show").join("Samples")));
+        assert!(rules.explain(&base.join("Show")).is_none());
+    }
+}
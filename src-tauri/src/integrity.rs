@@ -0,0 +1,354 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+use crate::database::Database;
+use crate::normalize_path;
+
+/// An episode within its season; files without a season marker count as season 1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpisodeNumber {
+    season: i64,
+    episode: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateEpisode {
+    season: i64,
+    episode: f64,
+    files: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryIntegrity {
+    entry_id: String,
+    title: String,
+    declared: i64,
+    present: i64,
+    missing: Vec<EpisodeNumber>,
+    duplicates: Vec<DuplicateEpisode>,
+    extras: Vec<EpisodeNumber>,
+    unnumbered: Vec<String>,
+}
+
+impl EntryIntegrity {
+    fn has_issues(&self) -> bool {
+        !self.missing.is_empty()
+            || !self.duplicates.is_empty()
+            || !self.extras.is_empty()
+            || !self.unnumbered.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    checked_entries: usize,
+    missing_episodes: usize,
+    duplicate_episodes: usize,
+    extra_episodes: usize,
+    /// Only entries with at least one issue are listed.
+    entries: Vec<EntryIntegrity>,
+}
+
+/// Parsed season, episode number and file name of each regular episode file.
+type EpisodeNumbers = Vec<(Option<i64>, Option<f64>, String)>;
+
+struct EntryRow {
+    id: String,
+    title: String,
+    declared: i64,
+}
+
+/// Compares the episode files found on disk with the count declared in the manifest.
+///
+/// Episodes are told apart by season and number, so `S01E05` and `S02E05` are different
+/// episodes. A single-season entry that is finished (`episodes > 0`) is checked against
+/// `1..=episodes`; ongoing or unknown ones, and each season of a multi-season entry, only
+/// report gaps below the highest episode present. Fractional numbers such as recap
+/// episodes (`06.5`) never count as missing or extra.
+fn check_entry(entry: EntryRow, files: EpisodeNumbers) -> EntryIntegrity {
+    let mut by_number = BTreeMap::<(i64, u64), (EpisodeNumber, Vec<String>)>::new();
+    let mut unnumbered = Vec::new();
+    for (season, number, file_name) in files {
+        let Some(number) = number else {
+            unnumbered.push(file_name);
+            continue;
+        };
+        let season = season.unwrap_or(1);
+        by_number
+            .entry((season, number.to_bits()))
+            .or_insert_with(|| (EpisodeNumber { season, episode: number }, Vec::new()))
+            .1
+            .push(file_name);
+    }
+
+    let mut whole_numbers = BTreeMap::<i64, BTreeSet<i64>>::new();
+    for (number, _) in by_number.values() {
+        if number.episode.fract() == 0.0 && number.episode > 0.0 {
+            whole_numbers.entry(number.season).or_default().insert(number.episode as i64);
+        }
+    }
+    // A declared count can only be matched against one season's numbering.
+    let declared = if whole_numbers.len() <= 1 { entry.declared } else { 0 };
+
+    let mut missing = Vec::new();
+    for (season, numbers) in &whole_numbers {
+        let expected_max = if declared > 0 {
+            declared
+        } else {
+            numbers.iter().next_back().copied().unwrap_or(0)
+        };
+        missing.extend((1..=expected_max).filter(|number| !numbers.contains(number)).map(|number| {
+            EpisodeNumber {
+                season: *season,
+                episode: number as f64,
+            }
+        }));
+    }
+    if whole_numbers.is_empty() && declared > 0 {
+        missing.extend((1..=declared).map(|number| EpisodeNumber {
+            season: 1,
+            episode: number as f64,
+        }));
+    }
+    let extras = if declared > 0 {
+        by_number
+            .values()
+            .filter(|(number, _)| number.episode.fract() == 0.0 && number.episode as i64 > declared)
+            .map(|(number, _)| *number)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let duplicates = by_number
+        .values()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(number, files)| DuplicateEpisode {
+            season: number.season,
+            episode: number.episode,
+            files: files.clone(),
+        })
+        .collect();
+
+    EntryIntegrity {
+        entry_id: entry.id,
+        title: entry.title,
+        declared: entry.declared,
+        present: by_number.len() as i64,
+        missing,
+        duplicates,
+        extras,
+        unnumbered,
+    }
+}
+
+/// The entries a check covers: a whole library root, or one entry.
+#[derive(Clone, Copy)]
+enum Scope<'a> {
+    Library(&'a str),
+    Entry(&'a str),
+}
+
+impl Scope<'_> {
+    fn entry_condition(&self) -> &'static str {
+        match self {
+            Scope::Library(_) => "library_root = ?",
+            Scope::Entry(_) => "id = ?",
+        }
+    }
+
+    fn episode_condition(&self) -> &'static str {
+        match self {
+            Scope::Library(_) => "library_root = ?",
+            Scope::Entry(_) => "entry_id = ?",
+        }
+    }
+
+    fn value(&self) -> &str {
+        match self {
+            Scope::Library(value) | Scope::Entry(value) => value,
+        }
+    }
+}
+
+fn load_entry_rows(conn: &Connection, scope: Scope) -> Result<Vec<EntryRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "
+            SELECT id, COALESCE(title, ''), COALESCE(CAST(episodes AS INTEGER), 0)
+            FROM manifest_entries
+            WHERE {}
+            ORDER BY group_sort_key, group_name, title_sort_key, title
+            ",
+            scope.entry_condition()
+        ))
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+    let rows = stmt
+        .query_map([scope.value()], |row| {
+            Ok(EntryRow {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                declared: row.get(2)?,
+            })
+        })
+        .map_err(|err| format!("Failed to read entries: {}", err))?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        entries.push(row.map_err(|err| format!("Failed to parse entry: {}", err))?);
+    }
+    Ok(entries)
+}
+
+fn load_episode_numbers(conn: &Connection, scope: Scope) -> Result<HashMap<String, EpisodeNumbers>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "
+            SELECT entry_id, season, episode_number, file_name
+            FROM episode_files
+            WHERE {} AND kind = 'episode'
+            ORDER BY file_name
+            ",
+            scope.episode_condition()
+        ))
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+    let rows = stmt
+        .query_map([scope.value()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<f64>>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|err| format!("Failed to read episodes: {}", err))?;
+
+    let mut episodes = HashMap::<String, EpisodeNumbers>::new();
+    for row in rows {
+        let (entry_id, season, number, file_name) =
+            row.map_err(|err| format!("Failed to parse episode: {}", err))?;
+        episodes.entry(entry_id).or_default().push((season, number, file_name));
+    }
+    Ok(episodes)
+}
+
+#[tauri::command]
+pub fn check_entry_integrity(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+) -> Result<EntryIntegrity, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    let scope = Scope::Entry(&entry_id);
+    let entry = load_entry_rows(&conn, scope)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Entry not found: {}", entry_id))?;
+    let files = load_episode_numbers(&conn, scope)?
+        .remove(&entry_id)
+        .unwrap_or_default();
+    Ok(check_entry(entry, files))
+}
+
+#[tauri::command]
+pub fn check_library_integrity(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+) -> Result<IntegrityReport, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let library_root = normalize_path(&base);
+    let conn = database.open(&base)?;
+    let scope = Scope::Library(&library_root);
+    let entries = load_entry_rows(&conn, scope)?;
+    let mut episodes = load_episode_numbers(&conn, scope)?;
+
+    let mut report = IntegrityReport {
+        checked_entries: entries.len(),
+        missing_episodes: 0,
+        duplicate_episodes: 0,
+        extra_episodes: 0,
+        entries: Vec::new(),
+    };
+    for entry in entries {
+        let files = episodes.remove(&entry.id).unwrap_or_default();
+        let result = check_entry(entry, files);
+        if !result.has_issues() {
+            continue;
+        }
+        report.missing_episodes += result.missing.len();
+        report.duplicate_episodes += result.duplicates.len();
+        report.extra_episodes += result.extras.len();
+        report.entries.push(result);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(declared: i64) -> EntryRow {
+        EntryRow {
+            id: "root::Title".to_string(),
+            title: "Title".to_string(),
+            declared,
+        }
+    }
+
+    fn file(season: Option<i64>, number: f64) -> (Option<i64>, Option<f64>, String) {
+        (season, Some(number), format!("S{:?}E{}.mkv", season, number))
+    }
+
+    #[test]
+    fn seasons_are_checked_separately() {
+        let files = (1..=3)
+            .map(|number| file(Some(1), number as f64))
+            .chain([1.0, 3.0].map(|number| file(Some(2), number)))
+            .collect();
+        let result = check_entry(entry(12), files);
+        assert!(result.duplicates.is_empty());
+        assert!(result.extras.is_empty());
+        assert_eq!(result.present, 5);
+        assert_eq!(result.missing, [EpisodeNumber { season: 2, episode: 2.0 }]);
+    }
+
+    #[test]
+    fn single_season_uses_the_declared_count() {
+        let files = vec![file(None, 1.0), file(Some(1), 1.0), file(None, 3.0), file(None, 5.0)];
+        let result = check_entry(entry(4), files);
+        assert_eq!(result.duplicates.len(), 1);
+        assert_eq!(result.missing, [2.0, 4.0].map(|episode| EpisodeNumber { season: 1, episode }));
+        assert_eq!(result.extras, [EpisodeNumber { season: 1, episode: 5.0 }]);
+    }
+
+    #[test]
+    fn entry_scope_loads_only_that_entry() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&conn).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO manifest_entries (id, library_root, title, episodes) VALUES ('r::A', '/lib', 'A', 2);
+            INSERT INTO manifest_entries (id, library_root, title, episodes) VALUES ('r::B', '/lib', 'B', 3);
+            INSERT INTO episode_files (path, entry_id, library_root, file_name, episode_number, kind)
+            VALUES ('/lib/A/01.mkv', 'r::A', '/lib', '01.mkv', 1, 'episode');
+            INSERT INTO episode_files (path, entry_id, library_root, file_name, episode_number, kind)
+            VALUES ('/lib/B/01.mkv', 'r::B', '/lib', '01.mkv', 1, 'episode');
+            ",
+        )
+        .unwrap();
+
+        let entries = load_entry_rows(&conn, Scope::Entry("r::B")).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.id.as_str()).collect::<Vec<_>>(), ["r::B"]);
+        let episodes = load_episode_numbers(&conn, Scope::Entry("r::B")).unwrap();
+        assert_eq!(episodes.keys().collect::<Vec<_>>(), ["r::B"]);
+
+        assert_eq!(load_entry_rows(&conn, Scope::Library("/lib")).unwrap().len(), 2);
+        assert_eq!(load_episode_numbers(&conn, Scope::Library("/lib")).unwrap().len(), 2);
+    }
+}
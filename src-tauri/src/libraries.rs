@@ -0,0 +1,655 @@
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use walkdir::WalkDir;
+
+use crate::history::write_atomic;
+use crate::watcher::LibraryWatcher;
+use crate::database::Database;
+use crate::ignore::IgnoreRules;
+use crate::manifest::ManifestNames;
+use crate::parallel::{default_concurrency, MAX_CONCURRENCY};
+use crate::{collation, load_diagnostics, load_entries, normalize_path, LibrarySnapshot};
+
+const REGISTRY_FILE_NAME: &str = "libraries.json";
+
+/// A library root as stored in the registry in the app's config directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisteredLibrary {
+    /// The root id kept in the library's database; survives drive letter and mount changes.
+    id: String,
+    name: String,
+    path: String,
+    enabled: bool,
+    added_at: i64,
+    /// Threads used to read manifests and size folders; `None` picks a default. Set it to 1
+    /// for libraries on spinning disks, where parallel reads only add seeks.
+    #[serde(default)]
+    scan_concurrency: Option<usize>,
+    /// Gitignore-style patterns skipped anywhere in the library, on top of the built-in ones.
+    #[serde(default)]
+    ignore_patterns: Vec<String>,
+    /// Descend into symlinked folders and junctions; loops are detected and reported.
+    #[serde(default)]
+    follow_symlinks: bool,
+    /// Don't cross into other drives or shares mounted inside the library.
+    #[serde(default)]
+    same_file_system: bool,
+    /// Deepest folder level scanned below the root; `None` for no limit.
+    #[serde(default)]
+    max_depth: Option<usize>,
+    /// Also accept the hidden `.anime.yml` / `.anime.yaml` as manifests.
+    #[serde(default)]
+    hidden_manifests: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LibraryRegistry {
+    #[serde(default)]
+    libraries: Vec<RegisteredLibrary>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryRoot {
+    #[serde(flatten)]
+    library: RegisteredLibrary,
+    /// Whether the root's folder is currently reachable.
+    available: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LibraryRootUpdate {
+    name: Option<String>,
+    path: Option<String>,
+    enabled: Option<bool>,
+    /// 0 goes back to the default.
+    scan_concurrency: Option<usize>,
+    ignore_patterns: Option<Vec<String>>,
+    follow_symlinks: Option<bool>,
+    same_file_system: Option<bool>,
+    /// 0 removes the limit.
+    max_depth: Option<usize>,
+    hidden_manifests: Option<bool>,
+}
+
+impl From<RegisteredLibrary> for LibraryRoot {
+    fn from(library: RegisteredLibrary) -> Self {
+        let available = Path::new(&library.path).is_dir();
+        Self { library, available }
+    }
+}
+
+/// Records which root the database indexes and returns its id.
+pub fn ensure_root(conn: &Connection, base: &Path) -> Result<String, String> {
+    let library_root = normalize_path(base);
+    if let Some(id) = find_root(conn, &library_root)? {
+        return Ok(id);
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|err| format!("Failed to start transaction: {}", err))?;
+    tx.execute(
+        "INSERT INTO library_roots (id, path, created_at) VALUES (?1, ?2, ?3)",
+        params![id, library_root, Utc::now().timestamp()],
+    )
+    .map_err(|err| format!("Failed to save library root: {}", err))?;
+
+    // Entries indexed before roots had ids were keyed by the root's path.
+    let legacy_prefix = format!("{}::", library_root);
+    let prefix = format!("{}::", id);
+    for (table, column) in [
+        ("manifest_entries", "id"),
+        ("manifest_files", "entry_id"),
+        ("episode_files", "entry_id"),
+        ("watch_progress", "entry_id"),
+        ("play_history", "entry_id"),
+    ] {
+        replace_prefix(&tx, table, column, &legacy_prefix, &prefix)?;
+    }
+    tx.commit()
+        .map_err(|err| format!("Failed to commit transaction: {}", err))?;
+    Ok(id)
+}
+
+fn find_root(conn: &Connection, library_root: &str) -> Result<Option<String>, String> {
+    // Every command resolves its root first, so keep this statement prepared.
+    conn.prepare_cached("SELECT id FROM library_roots WHERE path = ?1")
+        .and_then(|mut stmt| stmt.query_row([library_root], |row| row.get::<_, String>(0)).optional())
+        .map_err(|err| format!("Failed to read library root: {}", err))
+}
+
+/// Returns the id of the root indexed at `library_root`.
+pub fn root_id(conn: &Connection, library_root: &str) -> Result<String, String> {
+    find_root(conn, library_root)?.ok_or_else(|| format!("Library root not registered: {}", library_root))
+}
+
+/// Like `ensure_root`, for a database that lived inside the root it indexes. Such a file
+/// moves with its library, so a single root recorded under another path is the same
+/// library reached through a new drive letter or mount point.
+pub fn adopt_root(conn: &Connection, base: &Path) -> Result<String, String> {
+    let library_root = normalize_path(base);
+    let roots = {
+        let mut stmt = conn
+            .prepare("SELECT id, path FROM library_roots")
+            .map_err(|err| format!("Failed to prepare query: {}", err))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|err| format!("Failed to read library roots: {}", err))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("Failed to parse library root: {}", err))?
+    };
+    match roots.as_slice() {
+        [(id, previous)] if *previous != library_root => {
+            relocate_root(conn, id, previous, &library_root)?;
+            Ok(id.clone())
+        }
+        _ => ensure_root(conn, base),
+    }
+}
+
+/// Points a root at the folder its library now lives in, rewriting the stored paths.
+/// A root already recorded at that folder is replaced if it has nothing indexed yet.
+pub fn move_root(conn: &Connection, id: &str, base: &Path) -> Result<(), String> {
+    let library_root = normalize_path(base);
+    let previous = conn
+        .query_row("SELECT path FROM library_roots WHERE id = ?1", [id], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+        .map_err(|err| format!("Failed to read library root: {}", err))?
+        .ok_or_else(|| format!("Library root not registered: {}", id))?;
+    if previous == library_root {
+        return Ok(());
+    }
+
+    if let Some(other) = find_root(conn, &library_root)? {
+        let indexed = conn
+            .query_row(
+                "SELECT COUNT(*) FROM manifest_entries WHERE library_root = ?1",
+                [&library_root],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(|err| format!("Failed to count entries: {}", err))?;
+        if indexed > 0 {
+            return Err(format!("{} 已作为另一个资料库建立了索引", library_root));
+        }
+        delete_root(conn, &other)?;
+    }
+    relocate_root(conn, id, &previous, &library_root)
+}
+
+/// Removes a root and everything indexed or recorded for it.
+pub fn delete_root(conn: &Connection, id: &str) -> Result<(), String> {
+    let Some(library_root) = conn
+        .query_row("SELECT path FROM library_roots WHERE id = ?1", [id], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+        .map_err(|err| format!("Failed to read library root: {}", err))?
+    else {
+        return Ok(());
+    };
+    let prefix = format!("{}::", id);
+    for table in ["manifest_entries", "manifest_files", "manifest_history", "scan_diagnostics", "episode_files"] {
+        conn.execute(&format!("DELETE FROM {} WHERE library_root = ?1", table), [&library_root])
+            .map_err(|err| format!("Failed to clear {}: {}", table, err))?;
+    }
+    for table in ["watch_progress", "play_history"] {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE substr(entry_id, 1, length(?1)) = ?1", table),
+            [&prefix],
+        )
+        .map_err(|err| format!("Failed to clear {}: {}", table, err))?;
+    }
+    conn.execute("DELETE FROM library_roots WHERE id = ?1", [id])
+        .map_err(|err| format!("Failed to remove library root: {}", err))?;
+    Ok(())
+}
+
+fn relocate_root(conn: &Connection, id: &str, previous: &str, library_root: &str) -> Result<(), String> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|err| format!("Failed to start transaction: {}", err))?;
+    let entry_prefix = format!("{}::", id);
+    // Paths first, while `library_root` still tells this root's rows apart from the others.
+    for (table, column) in [
+        ("manifest_entries", "path"),
+        ("manifest_files", "path"),
+        ("manifest_history", "manifest_path"),
+        ("scan_diagnostics", "path"),
+        ("episode_files", "path"),
+    ] {
+        replace_path_prefix(&tx, table, column, "library_root = ?1", previous, previous, library_root)?;
+    }
+    for (table, column) in [("watch_progress", "file_path"), ("play_history", "last_played_path")] {
+        replace_path_prefix(
+            &tx,
+            table,
+            column,
+            "substr(entry_id, 1, length(?1)) = ?1",
+            &entry_prefix,
+            previous,
+            library_root,
+        )?;
+    }
+    for table in ["manifest_entries", "manifest_files", "manifest_history", "scan_diagnostics", "episode_files"] {
+        tx.execute(
+            &format!("UPDATE {} SET library_root = ?2 WHERE library_root = ?1", table),
+            params![previous, library_root],
+        )
+        .map_err(|err| format!("Failed to update {}.library_root: {}", table, err))?;
+    }
+    tx.execute(
+        "UPDATE library_roots SET path = ?2 WHERE id = ?1",
+        params![id, library_root],
+    )
+    .map_err(|err| format!("Failed to update library root: {}", err))?;
+    tx.commit()
+        .map_err(|err| format!("Failed to commit transaction: {}", err))
+}
+
+/// Rewrites paths in `column` from under `from` to under `to`, for the rows where `scope`
+/// holds with `?1` bound to `key`. Only whole path components match, so moving `D:\Anime`
+/// leaves `D:\Anime Movies` alone.
+fn replace_path_prefix(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    scope: &str,
+    key: &str,
+    from: &str,
+    to: &str,
+) -> Result<(), String> {
+    let ends_with_separator = from.ends_with(['/', '\\']);
+    conn.execute(
+        &format!(
+            "UPDATE {table} SET {column} = ?3 || substr({column}, length(?2) + 1)
+             WHERE {scope} AND (
+                 {column} = ?2
+                 OR (substr({column}, 1, length(?2)) = ?2
+                     AND (?4 OR substr({column}, length(?2) + 1, 1) IN ('/', '\\')))
+             )",
+        ),
+        params![key, from, to, ends_with_separator],
+    )
+    .map_err(|err| format!("Failed to update {}.{}: {}", table, column, err))?;
+    Ok(())
+}
+
+/// Rewrites `column` values starting with `from`; for id prefixes that end in `::`.
+fn replace_prefix(conn: &Connection, table: &str, column: &str, from: &str, to: &str) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "UPDATE {table} SET {column} = ?2 || substr({column}, length(?1) + 1)
+             WHERE substr({column}, 1, length(?1)) = ?1",
+        ),
+        params![from, to],
+    )
+    .map_err(|err| format!("Failed to update {}.{}: {}", table, column, err))?;
+    Ok(())
+}
+
+fn registry_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|err| format!("Failed to resolve config directory: {}", err))?;
+    Ok(dir.join(REGISTRY_FILE_NAME))
+}
+
+fn load_registry(app: &AppHandle) -> Result<LibraryRegistry, String> {
+    let path = registry_path(app)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(LibraryRegistry::default()),
+        Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+    };
+    serde_json::from_str(&content).map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+}
+
+fn save_registry(app: &AppHandle, registry: &LibraryRegistry) -> Result<(), String> {
+    let path = registry_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create {}: {}", parent.display(), err))?;
+    }
+    let content = serde_json::to_string_pretty(registry)
+        .map_err(|err| format!("Failed to serialize library registry: {}", err))?;
+    write_atomic(&path, content.as_bytes())
+}
+
+/// Opens the library at `path` and returns the id its database knows it by.
+fn identify_root(database: &Database, path: &Path) -> Result<String, String> {
+    if !path.is_dir() {
+        return Err(format!("Base directory not found: {}", path.display()));
+    }
+    let conn = database.open(path)?;
+    root_id(&conn, &normalize_path(path))
+}
+
+fn default_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| normalize_path(path))
+}
+
+#[tauri::command]
+pub fn list_libraries(app: AppHandle) -> Result<Vec<LibraryRoot>, String> {
+    Ok(load_registry(&app)?
+        .libraries
+        .into_iter()
+        .map(LibraryRoot::from)
+        .collect())
+}
+
+/// Registers a library root. Adding a folder that is already registered returns it,
+/// renamed if a name is given.
+#[tauri::command]
+pub fn add_library(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    path: String,
+    name: Option<String>,
+) -> Result<LibraryRoot, String> {
+    let base = PathBuf::from(path.trim());
+    let id = identify_root(&database, &base)?;
+    let library_path = normalize_path(&base);
+    let mut registry = load_registry(&app)?;
+
+    let name = name
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let library = match registry.libraries.iter_mut().find(|library| library.id == id) {
+        Some(library) => {
+            library.path = library_path;
+            if let Some(name) = name {
+                library.name = name;
+            }
+            library.clone()
+        }
+        None => {
+            let library = RegisteredLibrary {
+                id,
+                name: name.unwrap_or_else(|| default_name(&base)),
+                path: library_path,
+                enabled: true,
+                added_at: Utc::now().timestamp(),
+                scan_concurrency: None,
+                ignore_patterns: Vec::new(),
+                follow_symlinks: false,
+                same_file_system: false,
+                max_depth: None,
+                hidden_manifests: false,
+            };
+            registry.libraries.push(library.clone());
+            library
+        }
+    };
+    save_registry(&app, &registry)?;
+    app.state::<LibraryWatcher>().sync(&app)?;
+    Ok(library.into())
+}
+
+/// Renames, enables or disables a root, or points it at the folder it was moved to.
+#[tauri::command]
+pub fn update_library(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    id: String,
+    update: LibraryRootUpdate,
+) -> Result<LibraryRoot, String> {
+    let mut registry = load_registry(&app)?;
+    let library = registry
+        .libraries
+        .iter_mut()
+        .find(|library| library.id == id)
+        .ok_or_else(|| format!("Library not found: {}", id))?;
+
+    if let Some(name) = update.name {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("资料库名称不能为空".to_string());
+        }
+        library.name = name.to_string();
+    }
+    if let Some(path) = update.path {
+        let base = PathBuf::from(path.trim());
+        if !base.is_dir() {
+            return Err(format!("Base directory not found: {}", base.display()));
+        }
+        let conn = database.connection()?;
+        move_root(&conn, &id, &base)?;
+        library.path = normalize_path(&base);
+    }
+    if let Some(enabled) = update.enabled {
+        library.enabled = enabled;
+    }
+    if let Some(concurrency) = update.scan_concurrency {
+        library.scan_concurrency = Some(concurrency.min(MAX_CONCURRENCY)).filter(|value| *value > 0);
+    }
+    if let Some(patterns) = update.ignore_patterns {
+        library.ignore_patterns = patterns
+            .iter()
+            .map(|pattern| pattern.trim_end().to_string())
+            .filter(|pattern| !pattern.trim().is_empty())
+            .collect();
+    }
+    if let Some(follow_symlinks) = update.follow_symlinks {
+        library.follow_symlinks = follow_symlinks;
+    }
+    if let Some(same_file_system) = update.same_file_system {
+        library.same_file_system = same_file_system;
+    }
+    if let Some(max_depth) = update.max_depth {
+        library.max_depth = Some(max_depth).filter(|value| *value > 0);
+    }
+    if let Some(hidden_manifests) = update.hidden_manifests {
+        library.hidden_manifests = hidden_manifests;
+    }
+
+    let library = library.clone();
+    save_registry(&app, &registry)?;
+    app.state::<LibraryWatcher>().sync(&app)?;
+    Ok(library.into())
+}
+
+/// How the scanner treats the files of one library.
+pub struct ScanSettings {
+    root: PathBuf,
+    pub concurrency: usize,
+    pub ignore: IgnoreRules,
+    pub manifest_names: ManifestNames,
+    pub follow_symlinks: bool,
+    pub same_file_system: bool,
+    pub max_depth: Option<usize>,
+}
+
+impl ScanSettings {
+    /// The settings of a folder outside every registered root.
+    pub fn defaults(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            concurrency: default_concurrency(),
+            ignore: IgnoreRules::new(root, &[]),
+            manifest_names: ManifestNames::default(),
+            follow_symlinks: false,
+            same_file_system: false,
+            max_depth: None,
+        }
+    }
+
+    /// A walker over `root` with the library's link, mount and depth settings applied.
+    pub fn walk_dir(&self, root: &Path) -> WalkDir {
+        let mut walker = WalkDir::new(root)
+            .follow_links(self.follow_symlinks)
+            .same_file_system(self.same_file_system);
+        if let Some(max_depth) = self.max_depth {
+            // Depth counts from the library root, also when only a sub-folder is rescanned.
+            let offset = root
+                .strip_prefix(&self.root)
+                .map(|rest| rest.components().count())
+                .unwrap_or(0);
+            walker = walker.max_depth(max_depth.saturating_sub(offset));
+        }
+        walker
+    }
+}
+
+/// Scanner settings for `path`, taken from the registered root containing it. A folder
+/// outside every root gets the defaults, with its own `.animeignore` files still honored.
+pub fn scan_settings(app: &AppHandle, path: &Path) -> ScanSettings {
+    let library = load_registry(app).ok().and_then(|registry| {
+        registry
+            .libraries
+            .into_iter()
+            .filter(|library| path.starts_with(&library.path))
+            .max_by_key(|library| library.path.len())
+    });
+    match library {
+        Some(library) => ScanSettings {
+            root: PathBuf::from(&library.path),
+            concurrency: library.scan_concurrency.unwrap_or_else(default_concurrency),
+            ignore: IgnoreRules::new(Path::new(&library.path), &library.ignore_patterns),
+            manifest_names: ManifestNames::new(library.hidden_manifests),
+            follow_symlinks: library.follow_symlinks,
+            same_file_system: library.same_file_system,
+            max_depth: library.max_depth,
+        },
+        None => ScanSettings::defaults(path),
+    }
+}
+
+/// Unregisters a root. Its index database stays in place, so adding it back keeps its history.
+#[tauri::command]
+pub fn remove_library(app: AppHandle, id: String) -> Result<(), String> {
+    let mut registry = load_registry(&app)?;
+    let before = registry.libraries.len();
+    registry.libraries.retain(|library| library.id != id);
+    if registry.libraries.len() == before {
+        return Err(format!("Library not found: {}", id));
+    }
+    save_registry(&app, &registry)?;
+    app.state::<LibraryWatcher>().sync(&app)
+}
+
+/// Ids and folders of the enabled roots that are currently reachable.
+pub fn watched_roots(app: &AppHandle) -> Result<Vec<(String, PathBuf)>, String> {
+    Ok(load_registry(app)?
+        .libraries
+        .into_iter()
+        .filter(|library| library.enabled && Path::new(&library.path).is_dir())
+        .map(|library| (library.id, PathBuf::from(library.path)))
+        .collect())
+}
+
+/// Loads every enabled root that is currently reachable into one snapshot.
+pub fn load_all_libraries(app: &AppHandle, database: &Database) -> Result<LibrarySnapshot, String> {
+    let mut snapshot = LibrarySnapshot {
+        entries: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for library in load_registry(app)?.libraries {
+        let base = PathBuf::from(&library.path);
+        if !library.enabled || !base.is_dir() {
+            continue;
+        }
+        let conn = database.open(&base)?;
+        let library_root = normalize_path(&base);
+        snapshot.entries.extend(load_entries(&conn, &library_root)?);
+        snapshot.diagnostics.extend(load_diagnostics(&conn, &library_root)?);
+    }
+    snapshot.entries.sort_by_cached_key(|entry| {
+        (
+            collation::sort_key(&entry.group),
+            collation::sort_key(&entry.title),
+            entry.id.clone(),
+        )
+    });
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_root_rows(conn: &Connection, id: &str, root: &str) {
+        let entry = format!("{}::Frieren", id);
+        let dir = format!("{}/Frieren", root);
+        let episode = format!("{}/01.mkv", dir);
+        conn.execute(
+            "INSERT INTO manifest_entries (id, library_root, path) VALUES (?1, ?2, ?3)",
+            params![entry, root, dir],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO manifest_files (path, library_root, entry_id) VALUES (?1, ?2, ?3)",
+            params![format!("{}/manifest.yml", dir), root, entry],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO episode_files (path, entry_id, library_root) VALUES (?1, ?2, ?3)",
+            params![episode, entry, root],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO watch_progress (entry_id, file_path, watched) VALUES (?1, ?2, 1)",
+            params![entry, episode],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO play_history (entry_id, last_played_path) VALUES (?1, ?2)",
+            params![entry, episode],
+        )
+        .unwrap();
+    }
+
+    fn paths(conn: &Connection, id: &str) -> Vec<String> {
+        let entry = format!("{}::Frieren", id);
+        [
+            "SELECT library_root || '|' || path FROM manifest_entries WHERE id = ?1",
+            "SELECT library_root || '|' || path FROM manifest_files WHERE entry_id = ?1",
+            "SELECT library_root || '|' || path FROM episode_files WHERE entry_id = ?1",
+            "SELECT file_path FROM watch_progress WHERE entry_id = ?1",
+            "SELECT last_played_path FROM play_history WHERE entry_id = ?1",
+        ]
+        .iter()
+        .map(|sql| conn.query_row(sql, [&entry], |row| row.get::<_, String>(0)).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn moving_a_root_leaves_roots_sharing_its_prefix_alone() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&conn).unwrap();
+        let anime = ensure_root(&conn, Path::new("/lib/Anime")).unwrap();
+        let movies = ensure_root(&conn, Path::new("/lib/Anime Movies")).unwrap();
+        insert_root_rows(&conn, &anime, "/lib/Anime");
+        insert_root_rows(&conn, &movies, "/lib/Anime Movies");
+        let movies_before = paths(&conn, &movies);
+
+        move_root(&conn, &anime, Path::new("/mnt/Anime")).unwrap();
+
+        assert_eq!(paths(&conn, &movies), movies_before);
+        assert_eq!(
+            paths(&conn, &anime),
+            [
+                "/mnt/Anime|/mnt/Anime/Frieren",
+                "/mnt/Anime|/mnt/Anime/Frieren/manifest.yml",
+                "/mnt/Anime|/mnt/Anime/Frieren/01.mkv",
+                "/mnt/Anime/Frieren/01.mkv",
+                "/mnt/Anime/Frieren/01.mkv",
+            ]
+        );
+        assert_eq!(root_id(&conn, "/lib/Anime Movies").unwrap(), movies);
+        assert_eq!(root_id(&conn, "/mnt/Anime").unwrap(), anime);
+    }
+}
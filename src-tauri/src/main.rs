@@ -4,13 +4,43 @@ use chrono::Utc;
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::atomic::Ordering as AtomicOrdering;
+use tauri::Manager;
+
+use database::Database;
+use episodes::{is_video_file, parse_episode};
+use manifest::{ManifestFormat, ManifestMetadata, MetadataUpdate, RawMetadata, ScalarValue, StringList};
+
+mod collation;
+mod database;
+mod discover;
+mod episodes;
+mod history;
+mod identity;
+mod ignore;
+mod integrity;
+mod libraries;
+mod manifest;
+mod manifest_patch;
+mod migrations;
+mod parallel;
+mod progress;
+mod query;
+mod scan;
+mod search;
+mod storage;
+mod watcher;
+
+const MANIFEST_FILE_NAME: &str = "manifest.yml";
+const DB_FILE_NAME: &str = "anime-manager.sqlite";
 
 #[derive(Debug, Deserialize)]
 struct ManifestRaw {
+    id: Option<ScalarValue>,
     title: Option<String>,
     #[serde(default)]
     is_parent: bool,
@@ -19,6 +49,15 @@ struct ManifestRaw {
     episodes: Option<EpisodesValue>,
     quality: Option<String>,
     note: Option<String>,
+    aliases: Option<StringList>,
+    original_title: Option<String>,
+    year: Option<ScalarValue>,
+    season: Option<ScalarValue>,
+    studio: Option<String>,
+    tags: Option<StringList>,
+    source: Option<String>,
+    rating: Option<ScalarValue>,
+    external_ids: Option<BTreeMap<String, Option<ScalarValue>>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,10 +67,14 @@ enum EpisodesValue {
     Str(String),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct LibraryEntry {
     id: String,
+    /// Id of the library root the entry belongs to.
+    library_id: String,
+    /// The manifest's own `id`, which follows the folder when it is renamed or moved.
+    uid: String,
     title: String,
     fansub: String,
     subtitle_type: String,
@@ -45,6 +88,17 @@ struct LibraryEntry {
     last_played_path: String,
     last_played_name: String,
     last_played_at: i64,
+    available_episodes: i64,
+    total_size: i64,
+    added_at: i64,
+    watched_episodes: i64,
+    next_episode_path: String,
+    next_episode_name: String,
+    next_episode_number: Option<f64>,
+    /// Content hash of `manifest.yml` when it was indexed; sent back when editing.
+    manifest_revision: String,
+    #[serde(flatten)]
+    metadata: ManifestMetadata,
 }
 
 #[derive(Debug, Serialize)]
@@ -75,13 +129,14 @@ struct NewAnimePayload {
     quality: Option<String>,
     note: Option<String>,
     is_finished: bool,
+    #[serde(flatten)]
+    metadata: MetadataUpdate,
 }
 
 #[derive(Debug, Serialize)]
 struct ManifestWriteModel {
+    schema_version: u64,
     title: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    is_parent: Option<bool>,
     fansub: String,
     subtitle_type: String,
     episodes: i64,
@@ -89,6 +144,153 @@ struct ManifestWriteModel {
     note: String,
 }
 
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshSummary {
+    added: Vec<String>,
+    updated: Vec<String>,
+    removed: Vec<String>,
+    unchanged: usize,
+    episodes_changed: Vec<String>,
+    /// Renamed or moved entries whose history was carried over; not listed as added or removed.
+    moved: Vec<identity::EntryMove>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshResult {
+    entries: Vec<LibraryEntry>,
+    summary: RefreshSummary,
+    diagnostics: Vec<ScanDiagnostic>,
+    /// Paths left out by ignore patterns; nothing below a skipped folder is listed.
+    skipped: Vec<ignore::SkippedPath>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LibrarySnapshot {
+    entries: Vec<LibraryEntry>,
+    diagnostics: Vec<ScanDiagnostic>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DiagnosticKind {
+    Io,
+    Encoding,
+    /// A manifest that doesn't parse, whether YAML, JSON or TOML; stored as `yaml` as it
+    /// was before the other formats.
+    Yaml,
+    /// A symlinked folder that wasn't followed, or one that loops back on itself.
+    Link,
+    /// A folder already indexed under another path.
+    Duplicate,
+    /// A folder with more than one manifest.
+    Conflict,
+}
+
+impl DiagnosticKind {
+    fn as_db(self) -> &'static str {
+        match self {
+            DiagnosticKind::Io => "io",
+            DiagnosticKind::Encoding => "encoding",
+            DiagnosticKind::Yaml => "yaml",
+            DiagnosticKind::Link => "link",
+            DiagnosticKind::Duplicate => "duplicate",
+            DiagnosticKind::Conflict => "conflict",
+        }
+    }
+
+    fn from_db(value: &str) -> Self {
+        match value {
+            "encoding" => DiagnosticKind::Encoding,
+            "yaml" => DiagnosticKind::Yaml,
+            "link" => DiagnosticKind::Link,
+            "duplicate" => DiagnosticKind::Duplicate,
+            "conflict" => DiagnosticKind::Conflict,
+            _ => DiagnosticKind::Io,
+        }
+    }
+}
+
+/// A manifest (or directory) the scanner could not index.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanDiagnostic {
+    path: String,
+    kind: DiagnosticKind,
+    message: String,
+    line: Option<i64>,
+    column: Option<i64>,
+}
+
+impl ScanDiagnostic {
+    fn new(path: &Path, kind: DiagnosticKind, message: String) -> Self {
+        Self {
+            path: normalize_path(path),
+            kind,
+            message,
+            line: None,
+            column: None,
+        }
+    }
+
+    fn io(path: &Path, err: impl std::fmt::Display) -> Self {
+        Self::new(path, DiagnosticKind::Io, err.to_string())
+    }
+
+    fn encoding(path: &Path, err: std::string::FromUtf8Error) -> Self {
+        Self::new(path, DiagnosticKind::Encoding, format!("Manifest is not valid UTF-8: {}", err))
+    }
+
+    fn walk(path: &Path, err: walkdir::Error) -> Self {
+        match err.loop_ancestor() {
+            Some(ancestor) => Self::new(
+                path,
+                DiagnosticKind::Link,
+                format!("Link loops back to {}; not followed", ancestor.display()),
+            ),
+            None => Self::io(path, err),
+        }
+    }
+
+    fn parse(path: &Path, err: &manifest::ManifestError) -> Self {
+        let location = err.location();
+        Self {
+            line: location.map(|(line, _)| line as i64),
+            column: location.map(|(_, column)| column as i64),
+            ..Self::new(path, DiagnosticKind::Yaml, err.to_string())
+        }
+    }
+}
+
+/// Last seen state of a `manifest.yml`, used to skip re-parsing unchanged files.
+#[derive(Debug, Clone)]
+struct ManifestFileRecord {
+    entry_id: String,
+    is_parent: bool,
+    parent_title: String,
+    modified_at: i64,
+    size: i64,
+    content_hash: String,
+}
+
+impl ManifestFileRecord {
+    fn mark_unchanged(
+        &self,
+        manifest_path: &Path,
+        parent_manifest_titles: &mut HashMap<String, String>,
+        unchanged_ids: &mut Vec<String>,
+    ) {
+        if self.is_parent {
+            let parent_dir = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+            parent_manifest_titles.insert(normalize_path(parent_dir), self.parent_title.clone());
+        } else {
+            unchanged_ids.push(self.entry_id.clone());
+        }
+    }
+}
+
 fn normalize(value: Option<String>) -> String {
     value.unwrap_or_default().trim().to_string()
 }
@@ -105,7 +307,7 @@ fn normalize_path(path: &Path) -> String {
     path.to_string_lossy().to_string()
 }
 
-fn build_entry(base_dir: &Path, manifest_path: &Path, raw: ManifestRaw) -> LibraryEntry {
+fn build_entry(base_dir: &Path, root_id: &str, manifest_path: &Path, raw: ManifestRaw) -> LibraryEntry {
     let parent = manifest_path
         .parent()
         .unwrap_or_else(|| Path::new(""));
@@ -131,11 +333,23 @@ fn build_entry(base_dir: &Path, manifest_path: &Path, raw: ManifestRaw) -> Libra
         }
     };
 
-    let library_root = normalize_path(base_dir);
-    let id = format!("{}::{}", library_root, relative_dir);
+    let id = format!("{}::{}", root_id, relative_dir);
+    let metadata = ManifestMetadata::from_raw(RawMetadata {
+        aliases: raw.aliases,
+        original_title: raw.original_title,
+        year: raw.year,
+        season: raw.season,
+        studio: raw.studio,
+        tags: raw.tags,
+        source: raw.source,
+        rating: raw.rating,
+        external_ids: raw.external_ids,
+    });
 
     LibraryEntry {
         id,
+        library_id: root_id.to_string(),
+        uid: raw.id.map(ScalarValue::into_text).unwrap_or_default(),
         title,
         fansub: normalize(raw.fansub),
         subtitle_type: normalize(raw.subtitle_type),
@@ -149,6 +363,15 @@ fn build_entry(base_dir: &Path, manifest_path: &Path, raw: ManifestRaw) -> Libra
         last_played_path: String::new(),
         last_played_name: String::new(),
         last_played_at: 0,
+        available_episodes: 0,
+        total_size: 0,
+        added_at: 0,
+        watched_episodes: 0,
+        next_episode_path: String::new(),
+        next_episode_name: String::new(),
+        next_episode_number: None,
+        manifest_revision: String::new(),
+        metadata,
     }
 }
 
@@ -191,189 +414,723 @@ fn resolve_group_title(
     default_group_from_relative(&entry.relative_dir, &entry.folder_name)
 }
 
-fn db_path(base_dir: &Path) -> PathBuf {
-    base_dir.join("anime-manager.sqlite")
+fn parent_manifest_title(parent_dir: &Path, title: Option<String>) -> String {
+    let candidate = normalize(title);
+    if candidate.is_empty() {
+        parent_dir
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default()
+    } else {
+        candidate
+    }
 }
 
-fn open_db(base_dir: &Path) -> Result<Connection, String> {
-    if !base_dir.exists() {
-        return Err(format!("Base directory not found: {}", base_dir.display()));
-    }
-    let conn = Connection::open(db_path(base_dir))
-        .map_err(|err| format!("Failed to open database: {}", err))?;
-    init_db(&conn)?;
-    Ok(conn)
+fn file_fingerprint(metadata: &fs::Metadata) -> (i64, i64) {
+    let modified_at = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|value| value.as_millis() as i64)
+        .unwrap_or(0);
+    (modified_at, metadata.len() as i64)
 }
 
-fn init_db(conn: &Connection) -> Result<(), String> {
-    conn.execute_batch(
-        "
-        CREATE TABLE IF NOT EXISTS manifest_entries (
-            id TEXT PRIMARY KEY,
-            library_root TEXT NOT NULL,
-            title TEXT,
-            fansub TEXT,
-            subtitle_type TEXT,
-            episodes INTEGER,
-            quality TEXT,
-            note TEXT,
-            path TEXT,
-            folder_name TEXT,
-            group_name TEXT,
-            relative_dir TEXT,
-            updated_at INTEGER
-        );
-        CREATE INDEX IF NOT EXISTS idx_manifest_library ON manifest_entries (library_root);
-        CREATE TABLE IF NOT EXISTS play_history (
-            entry_id TEXT PRIMARY KEY,
-            last_played_path TEXT,
-            last_played_name TEXT,
-            updated_at INTEGER
-        );
-        ",
-    )
-    .map_err(|err| format!("Failed to init database: {}", err))?;
-    Ok(())
+fn hash_content(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Matches the index database and its journal files, which earlier versions kept inside
+/// the library root and which may still be configured to live there.
+fn is_index_database_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with(DB_FILE_NAME))
+        .unwrap_or(false)
+}
+
+/// Columns read by `read_entry`; callers append their own filtering and ordering.
+pub(crate) const ENTRY_SELECT: &str = "
+    SELECT
+        m.id,
+        m.title,
+        m.fansub,
+        m.subtitle_type,
+        COALESCE(CAST(m.episodes AS INTEGER), 0),
+        m.quality,
+        m.note,
+        m.path,
+        m.folder_name,
+        m.group_name,
+        m.relative_dir,
+        COALESCE(p.last_played_path, ''),
+        COALESCE(p.last_played_name, ''),
+        COALESCE(p.updated_at, 0),
+        COALESCE(e.available, 0),
+        COALESCE(e.total_size, 0),
+        COALESCE(m.added_at, m.updated_at, 0),
+        COALESCE(m.aliases, '[]'),
+        COALESCE(m.original_title, ''),
+        m.year,
+        COALESCE(m.season, ''),
+        COALESCE(m.studio, ''),
+        COALESCE(m.tags, '[]'),
+        COALESCE(m.source, ''),
+        m.rating,
+        COALESCE(m.external_ids, '{}'),
+        COALESCE(f.content_hash, ''),
+        COALESCE(m.uid, '')
+    FROM manifest_entries m
+    LEFT JOIN play_history p ON m.id = p.entry_id
+    LEFT JOIN manifest_files f ON f.entry_id = m.id
+    LEFT JOIN (
+        SELECT
+            entry_id,
+            COUNT(DISTINCT CASE WHEN kind = 'episode' THEN episode_number END) AS available,
+            SUM(size) AS total_size
+        FROM episode_files
+        GROUP BY entry_id
+    ) e ON m.id = e.entry_id
+";
+
+pub(crate) fn read_entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<LibraryEntry> {
+    let id: String = row.get(0)?;
+    // Ids are `{root id}::{relative dir}`.
+    let library_id = id.split_once("::").map(|(root, _)| root.to_string()).unwrap_or_default();
+    Ok(LibraryEntry {
+        id,
+        library_id,
+        uid: row.get(27)?,
+        title: row.get(1)?,
+        fansub: row.get(2)?,
+        subtitle_type: row.get(3)?,
+        episodes: row.get(4)?,
+        quality: row.get(5)?,
+        note: row.get(6)?,
+        path: row.get(7)?,
+        folder_name: row.get(8)?,
+        group: row.get(9)?,
+        relative_dir: row.get(10)?,
+        last_played_path: row.get(11)?,
+        last_played_name: row.get(12)?,
+        last_played_at: row.get(13)?,
+        available_episodes: row.get(14)?,
+        total_size: row.get(15)?,
+        added_at: row.get(16)?,
+        watched_episodes: 0,
+        next_episode_path: String::new(),
+        next_episode_name: String::new(),
+        next_episode_number: None,
+        manifest_revision: row.get(26)?,
+        metadata: ManifestMetadata {
+            aliases: json_column(row.get(17)?),
+            original_title: row.get(18)?,
+            year: row.get(19)?,
+            season: row.get(20)?,
+            studio: row.get(21)?,
+            tags: json_column(row.get(22)?),
+            source: row.get(23)?,
+            rating: row.get(24)?,
+            external_ids: json_column(row.get(25)?),
+        },
+    })
+}
+
+/// Lists and maps are stored as JSON text; a malformed value reads as empty.
+fn json_column<T: serde::de::DeserializeOwned + Default>(value: String) -> T {
+    serde_json::from_str(&value).unwrap_or_default()
+}
+
+/// Drains entry rows and fills in each entry's watch progress.
+pub(crate) fn collect_entries(
+    conn: &Connection,
+    rows: impl Iterator<Item = rusqlite::Result<LibraryEntry>>,
+) -> Result<Vec<LibraryEntry>, String> {
+    let mut entries = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Failed to parse entry: {}", err))?;
+    let ids = entries.iter().map(|entry| entry.id.clone()).collect::<Vec<_>>();
+    let mut progress = progress::load_entry_progress(conn, &ids)?;
+    for entry in &mut entries {
+        if let Some(item) = progress.remove(&entry.id) {
+            entry.watched_episodes = item.watched_episodes;
+            if let Some(next) = item.next {
+                entry.next_episode_path = next.path;
+                entry.next_episode_name = next.file_name;
+                entry.next_episode_number = next.number;
+            }
+        }
+    }
+
+    Ok(entries)
 }
 
 fn load_entries(conn: &Connection, library_root: &str) -> Result<Vec<LibraryEntry>, String> {
     let mut stmt = conn
-        .prepare(
+        .prepare_cached(&format!(
+            "{} WHERE m.library_root = ? ORDER BY m.group_sort_key, m.group_name, m.title_sort_key, m.title",
+            ENTRY_SELECT
+        ))
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+
+    let rows = stmt
+        .query_map([library_root], read_entry)
+        .map_err(|err| format!("Failed to read entries: {}", err))?;
+
+    collect_entries(conn, rows)
+}
+
+fn load_manifest_files(
+    conn: &Connection,
+    library_root: &str,
+) -> Result<HashMap<String, ManifestFileRecord>, String> {
+    let mut stmt = conn
+        .prepare_cached(
             "
             SELECT
-                m.id,
-                m.title,
-                m.fansub,
-                m.subtitle_type,
-                COALESCE(CAST(m.episodes AS INTEGER), 0),
-                m.quality,
-                m.note,
-                m.path,
-                m.folder_name,
-                m.group_name,
-                m.relative_dir,
-                COALESCE(p.last_played_path, ''),
-                COALESCE(p.last_played_name, ''),
-                COALESCE(p.updated_at, 0)
-            FROM manifest_entries m
-            LEFT JOIN play_history p ON m.id = p.entry_id
-            WHERE m.library_root = ?
-            ORDER BY m.group_name, m.title
+                path,
+                COALESCE(entry_id, ''),
+                is_parent,
+                COALESCE(parent_title, ''),
+                COALESCE(modified_at, 0),
+                COALESCE(size, 0),
+                COALESCE(content_hash, '')
+            FROM manifest_files
+            WHERE library_root = ?
             ",
         )
         .map_err(|err| format!("Failed to prepare query: {}", err))?;
 
     let rows = stmt
         .query_map([library_root], |row| {
-            Ok(LibraryEntry {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                fansub: row.get(2)?,
-                subtitle_type: row.get(3)?,
-                episodes: row.get(4)?,
-                quality: row.get(5)?,
-                note: row.get(6)?,
-                path: row.get(7)?,
-                folder_name: row.get(8)?,
-                group: row.get(9)?,
-                relative_dir: row.get(10)?,
-                last_played_path: row.get(11)?,
-                last_played_name: row.get(12)?,
-                last_played_at: row.get(13)?,
+            Ok((
+                row.get::<_, String>(0)?,
+                ManifestFileRecord {
+                    entry_id: row.get(1)?,
+                    is_parent: row.get(2)?,
+                    parent_title: row.get(3)?,
+                    modified_at: row.get(4)?,
+                    size: row.get(5)?,
+                    content_hash: row.get(6)?,
+                },
+            ))
+        })
+        .map_err(|err| format!("Failed to read manifest records: {}", err))?;
+
+    let mut records = HashMap::new();
+    for row in rows {
+        let (path, record) = row.map_err(|err| format!("Failed to parse manifest record: {}", err))?;
+        records.insert(path, record);
+    }
+
+    Ok(records)
+}
+
+/// Keeps the preferred manifest of each folder and reports folders that have several,
+/// such as `manifest.yml` next to `manifest.yaml`, or two case variants of one name.
+fn pick_manifests(
+    manifest_paths: Vec<PathBuf>,
+    names: &manifest::ManifestNames,
+    diagnostics: &mut Vec<ScanDiagnostic>,
+) -> Vec<PathBuf> {
+    let mut by_dir = BTreeMap::<PathBuf, Vec<PathBuf>>::new();
+    for path in manifest_paths {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        by_dir.entry(dir).or_default().push(path);
+    }
+
+    let mut picked = Vec::new();
+    for (dir, mut paths) in by_dir {
+        paths.sort_by_key(|path| (names.rank(path), path.clone()));
+        if paths.len() > 1 {
+            let file_names = paths
+                .iter()
+                .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+                .collect::<Vec<_>>();
+            diagnostics.push(ScanDiagnostic::new(
+                &dir,
+                DiagnosticKind::Conflict,
+                format!("Several manifests in one folder ({}); using {}", file_names.join(", "), file_names[0]),
+            ));
+        }
+        picked.extend(paths.into_iter().next());
+    }
+    picked
+}
+
+/// `fs::canonicalize` without the `\\?\` prefix Windows adds, so the result compares
+/// equal to the paths the walker produces.
+fn canonical_path(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path).ok().map(strip_verbatim)
+}
+
+#[cfg(windows)]
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let value = path.to_string_lossy();
+    if let Some(rest) = value.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{}", rest));
+    }
+    match value.strip_prefix(r"\\?\") {
+        Some(rest) => PathBuf::from(rest),
+        None => path.clone(),
+    }
+}
+
+#[cfg(not(windows))]
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// With links followed, one folder can be reached through several paths and would be
+/// indexed once per path under different ids. Keeps the path that is already canonical,
+/// or the shortest when none is, reports the others and returns them so nothing below
+/// them is indexed.
+///
+/// `indexed` holds the entry folders kept from outside the walked scope; a folder that
+/// resolves to one of them is a duplicate even though the walk never saw the other path.
+fn duplicate_dirs(
+    manifest_paths: &[PathBuf],
+    indexed: &HashSet<PathBuf>,
+    concurrency: usize,
+    diagnostics: &mut Vec<ScanDiagnostic>,
+) -> Vec<PathBuf> {
+    let dirs = manifest_paths
+        .iter()
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .collect::<Vec<PathBuf>>();
+    let targets = parallel::map_ordered(&dirs, concurrency, |dir| canonical_path(dir));
+    let mut reached = HashMap::<PathBuf, Vec<&PathBuf>>::new();
+    for (dir, target) in dirs.iter().zip(targets) {
+        if let Some(target) = target {
+            reached.entry(target).or_default().push(dir);
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for (target, mut paths) in reached {
+        let walked_canonical = paths.iter().any(|path| **path == target);
+        let kept = if !walked_canonical && indexed.contains(&target) {
+            target.clone()
+        } else if paths.len() > 1 {
+            paths.sort_by_key(|path| (**path != target, path.components().count(), path.to_path_buf()));
+            paths.remove(0).clone()
+        } else {
+            continue;
+        };
+        for path in paths {
+            diagnostics.push(ScanDiagnostic::new(
+                path,
+                DiagnosticKind::Duplicate,
+                format!("Same folder as {}; indexed only once", kept.display()),
+            ));
+            duplicates.push(path.to_path_buf());
+        }
+    }
+    duplicates
+}
+
+enum ManifestScan {
+    Unchanged(ManifestFileRecord),
+    Touched(ManifestFileRecord),
+    Changed(Box<ManifestRaw>, ManifestFileRecord),
+}
+
+fn scan_manifest(
+    manifest_path: &Path,
+    known: Option<&ManifestFileRecord>,
+) -> Result<ManifestScan, ScanDiagnostic> {
+    let metadata = fs::metadata(manifest_path).map_err(|err| ScanDiagnostic::io(manifest_path, err))?;
+    let (modified_at, size) = file_fingerprint(&metadata);
+
+    if let Some(record) = known {
+        if record.modified_at == modified_at && record.size == size {
+            return Ok(ManifestScan::Unchanged(record.clone()));
+        }
+    }
+
+    let content = fs::read(manifest_path).map_err(|err| ScanDiagnostic::io(manifest_path, err))?;
+    let content_hash = hash_content(&content);
+
+    if let Some(record) = known {
+        if record.content_hash == content_hash {
+            // Touched but not edited: keep the entry, just remember the new stat.
+            return Ok(ManifestScan::Touched(ManifestFileRecord {
+                modified_at,
+                size,
+                ..record.clone()
+            }));
+        }
+    }
+
+    let content = String::from_utf8(content).map_err(|err| ScanDiagnostic::encoding(manifest_path, err))?;
+    let raw = manifest::parse_manifest(&content, ManifestFormat::from_path(manifest_path))
+        .map_err(|err| ScanDiagnostic::parse(manifest_path, &err))?;
+    let record = ManifestFileRecord {
+        entry_id: String::new(),
+        is_parent: raw.is_parent,
+        parent_title: String::new(),
+        modified_at,
+        size,
+        content_hash,
+    };
+    Ok(ManifestScan::Changed(Box::new(raw), record))
+}
+
+fn load_diagnostics(conn: &Connection, library_root: &str) -> Result<Vec<ScanDiagnostic>, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "
+            SELECT path, kind, message, line, column
+            FROM scan_diagnostics
+            WHERE library_root = ?
+            ORDER BY path
+            ",
+        )
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+
+    let rows = stmt
+        .query_map([library_root], |row| {
+            Ok(ScanDiagnostic {
+                path: row.get(0)?,
+                kind: DiagnosticKind::from_db(&row.get::<_, String>(1)?),
+                message: row.get(2)?,
+                line: row.get(3)?,
+                column: row.get(4)?,
             })
         })
-        .map_err(|err| format!("Failed to read entries: {}", err))?;
+        .map_err(|err| format!("Failed to read diagnostics: {}", err))?;
 
-    let mut entries = Vec::new();
-    for entry in rows {
-        entries.push(entry.map_err(|err| format!("Failed to parse entry: {}", err))?);
+    let mut diagnostics = Vec::new();
+    for diagnostic in rows {
+        diagnostics.push(diagnostic.map_err(|err| format!("Failed to parse diagnostic: {}", err))?);
     }
 
-    Ok(entries)
+    Ok(diagnostics)
 }
 
+/// Loads one library root, or every enabled root in the registry when `base_dir` is omitted.
 #[tauri::command]
-fn load_library(base_dir: String) -> Result<Vec<LibraryEntry>, String> {
+fn load_library(
+    app: tauri::AppHandle,
+    database: tauri::State<'_, Database>,
+    base_dir: Option<String>,
+) -> Result<LibrarySnapshot, String> {
+    let Some(base_dir) = base_dir else {
+        return libraries::load_all_libraries(&app, &database);
+    };
     let base = PathBuf::from(base_dir.trim());
     let library_root = normalize_path(&base);
-    let conn = open_db(&base)?;
-    load_entries(&conn, &library_root)
+    let conn = database.open(&base)?;
+    Ok(LibrarySnapshot {
+        entries: load_entries(&conn, &library_root)?,
+        diagnostics: load_diagnostics(&conn, &library_root)?,
+    })
 }
 
-#[tauri::command]
-fn refresh_library(base_dir: String) -> Result<Vec<LibraryEntry>, String> {
-    let base = PathBuf::from(base_dir.trim());
+/// Rescans `scope` (the whole library when empty) and applies the difference to the index.
+///
+/// Manifests outside the scope are trusted as-is, so the watcher can refresh a single
+/// folder without walking the rest of the library. With a `reporter`, the walk reports
+/// its progress and stops without touching the index when the scan is cancelled.
+fn refresh_index(
+    database: &Database,
+    base: &Path,
+    scope: &[PathBuf],
+    full_rescan: bool,
+    settings: &libraries::ScanSettings,
+    mut reporter: Option<&mut scan::ScanReporter>,
+) -> Result<RefreshResult, String> {
     if !base.exists() {
         return Err(format!("Base directory not found: {}", base.display()));
     }
-    let library_root = normalize_path(&base);
-    let mut conn = open_db(&base)?;
+    let library_root = normalize_path(base);
+    let roots = if scope.is_empty() {
+        vec![base.to_path_buf()]
+    } else {
+        scope.to_vec()
+    };
+    let in_scope = |path: &str| roots.iter().any(|root| Path::new(path).starts_with(root));
+    let mut conn = database.open(base)?;
+    let root_id = libraries::root_id(&conn, &library_root)?;
+
+    let known_files = load_manifest_files(&conn, &library_root)?;
+    if let Some(reporter) = reporter.as_deref_mut() {
+        reporter.set_expected_manifests(known_files.len());
+    }
+    let mut existing = load_entries(&conn, &library_root)?
+        .into_iter()
+        .map(|entry| (entry.id.clone(), entry))
+        .collect::<HashMap<String, LibraryEntry>>();
+    let existing_paths = existing
+        .values()
+        .map(|entry| (entry.id.clone(), entry.path.clone()))
+        .collect::<HashMap<String, String>>();
+
+    let mut seen_files = HashSet::<String>::new();
+    let mut dirty_files = Vec::<(String, ManifestFileRecord)>::new();
+    let mut unchanged_ids = Vec::<String>::new();
+    let mut parent_manifest_titles = HashMap::<String, String>::new();
+    let mut parsed = Vec::<(PathBuf, Box<ManifestRaw>, ManifestFileRecord)>::new();
+
+    let mut diagnostics = load_diagnostics(&conn, &library_root)?;
+    diagnostics.retain(|diagnostic| !in_scope(&diagnostic.path));
 
-    let mut parsed = Vec::<(PathBuf, ManifestRaw)>::new();
-    for entry in WalkDir::new(&base).into_iter().filter_map(Result::ok) {
-        if !entry.file_type().is_file() {
+    for (path, record) in &known_files {
+        if in_scope(path) {
             continue;
         }
-        if entry.file_name() != "manifest.yml" {
+        if record.is_parent || existing.contains_key(&record.entry_id) {
+            seen_files.insert(path.clone());
+            record.mark_unchanged(Path::new(path), &mut parent_manifest_titles, &mut unchanged_ids);
+        }
+    }
+
+    // Walk the tree first; the per-file stats and manifest reads run in parallel below.
+    let mut video_paths = Vec::<PathBuf>::new();
+    let mut manifest_paths = Vec::<PathBuf>::new();
+    let mut skipped = Vec::<ignore::SkippedPath>::new();
+    for root in roots.iter().filter(|root| root.exists()) {
+        // A watcher scope can start inside an ignored folder, which a full walk never enters.
+        if let Some(reason) = settings.ignore.explain(root) {
+            skipped.push(reason);
             continue;
         }
+        let walker = settings.walk_dir(root).into_iter().filter_entry(|entry| {
+            match settings.ignore.check(entry.path(), entry.file_type().is_dir()) {
+                Some(reason) => {
+                    skipped.push(reason);
+                    false
+                }
+                None => true,
+            }
+        });
+        for entry in walker {
+            if let Some(reporter) = reporter.as_deref_mut() {
+                reporter.check_cancelled()?;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    let path = err.path().unwrap_or(base).to_path_buf();
+                    diagnostics.push(ScanDiagnostic::walk(&path, err));
+                    continue;
+                }
+            };
+            if entry.path_is_symlink() && !settings.follow_symlinks && entry.path().is_dir() {
+                diagnostics.push(ScanDiagnostic::new(
+                    entry.path(),
+                    DiagnosticKind::Link,
+                    "Symbolic link or junction to a folder; not followed".to_string(),
+                ));
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                if let Some(reporter) = reporter.as_deref_mut().filter(|_| entry.file_type().is_dir()) {
+                    reporter.visit_directory(entry.path());
+                }
+                continue;
+            }
+            if is_video_file(entry.path()) {
+                video_paths.push(entry.into_path());
+            } else if settings.manifest_names.is_manifest(entry.path()) {
+                if let Some(reporter) = reporter.as_deref_mut() {
+                    reporter.found_manifest(entry.path());
+                }
+                manifest_paths.push(entry.into_path());
+            }
+        }
+    }
+    if let Some(reporter) = reporter.as_deref_mut() {
+        reporter.check_cancelled()?;
+        reporter.finish_walk(base);
+    }
 
-        let content = fs::read_to_string(entry.path())
-            .map_err(|err| format!("Failed to read {}: {}", entry.path().display(), err))?;
-        let raw: ManifestRaw = serde_yaml::from_str(&content)
-            .map_err(|err| format!("Invalid YAML {}: {}", entry.path().display(), err))?;
-        parsed.push((entry.path().to_path_buf(), raw));
+    let mut manifest_paths = pick_manifests(manifest_paths, &settings.manifest_names, &mut diagnostics);
+    if settings.follow_symlinks {
+        let indexed = existing_paths
+            .values()
+            .filter(|path| !in_scope(path))
+            .map(PathBuf::from)
+            .collect::<HashSet<PathBuf>>();
+        let duplicates = duplicate_dirs(&manifest_paths, &indexed, settings.concurrency, &mut diagnostics);
+        let reached_twice = |path: &Path| duplicates.iter().any(|dir| path.starts_with(dir));
+        manifest_paths.retain(|path| !reached_twice(path));
+        video_paths.retain(|path| !reached_twice(path));
     }
 
-    let mut parent_manifest_titles = HashMap::<String, String>::new();
-    for (manifest_path, raw) in &parsed {
+    let cancelled = reporter.as_deref().map(scan::ScanReporter::cancelled_flag);
+    let is_cancelled = || cancelled.map(|flag| flag.load(AtomicOrdering::Relaxed)).unwrap_or(false);
+    let videos = parallel::map_ordered(&video_paths, settings.concurrency, |path| {
+        if is_cancelled() {
+            return None;
+        }
+        let (modified_at, size) = file_fingerprint(&fs::metadata(path).ok()?);
+        Some((path.clone(), modified_at, size))
+    })
+    .into_iter()
+    .flatten()
+    .collect::<Vec<(PathBuf, i64, i64)>>();
+
+    // A record is only trusted when the entry it produced is still in the index.
+    let manifest_jobs = manifest_paths
+        .into_iter()
+        .map(|path| {
+            let key = normalize_path(&path);
+            let previous = known_files
+                .get(&key)
+                .filter(|record| record.is_parent || existing.contains_key(&record.entry_id));
+            (path, key, previous)
+        })
+        .collect::<Vec<_>>();
+    let scans = parallel::map_ordered(&manifest_jobs, settings.concurrency, |(manifest_path, _, previous)| {
+        if is_cancelled() {
+            return None;
+        }
+        Some(scan_manifest(manifest_path, previous.filter(|_| !full_rescan)))
+    });
+    if let Some(reporter) = reporter.as_deref() {
+        reporter.check_cancelled()?;
+    }
+
+    for ((manifest_path, key, previous), scan) in manifest_jobs.into_iter().zip(scans) {
+        let Some(scan) = scan else {
+            continue;
+        };
+        seen_files.insert(key.clone());
+        match scan {
+            Ok(ManifestScan::Unchanged(record)) => {
+                record.mark_unchanged(&manifest_path, &mut parent_manifest_titles, &mut unchanged_ids);
+            }
+            Ok(ManifestScan::Touched(record)) => {
+                record.mark_unchanged(&manifest_path, &mut parent_manifest_titles, &mut unchanged_ids);
+                dirty_files.push((key, record));
+            }
+            Ok(ManifestScan::Changed(raw, record)) => {
+                parsed.push((manifest_path, raw, record));
+            }
+            Err(diagnostic) => {
+                // Keep serving the last good version of a manifest that is now broken.
+                if let Some(record) = previous {
+                    record.mark_unchanged(&manifest_path, &mut parent_manifest_titles, &mut unchanged_ids);
+                }
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    for (manifest_path, raw, record) in &mut parsed {
         if !raw.is_parent {
             continue;
         }
         let parent_dir = manifest_path
             .parent()
             .unwrap_or_else(|| Path::new(""));
-        let fallback = parent_dir
-            .file_name()
-            .map(|v| v.to_string_lossy().to_string())
-            .unwrap_or_default();
-        let title = {
-            let candidate = normalize(raw.title.clone());
-            if candidate.is_empty() {
-                fallback
-            } else {
-                candidate
-            }
-        };
+        let title = parent_manifest_title(parent_dir, raw.title.clone());
+        record.parent_title = title.clone();
         parent_manifest_titles.insert(normalize_path(parent_dir), title);
     }
 
-    let mut entries = Vec::new();
-    for (manifest_path, raw) in parsed {
+    let mut summary = RefreshSummary::default();
+    let mut changed = Vec::<LibraryEntry>::new();
+    for (manifest_path, raw, mut record) in parsed {
+        let key = normalize_path(&manifest_path);
         if raw.is_parent {
+            dirty_files.push((key, record));
             continue;
         }
-        let mut entry = build_entry(&base, &manifest_path, raw);
-        entry.group = resolve_group_title(&base, &entry, &parent_manifest_titles);
-        entries.push(entry);
+        let mut entry = build_entry(base, &root_id, &manifest_path, *raw);
+        entry.group = resolve_group_title(base, &entry, &parent_manifest_titles);
+        if existing_paths.contains_key(&entry.id) {
+            summary.updated.push(entry.id.clone());
+        } else {
+            summary.added.push(entry.id.clone());
+        }
+        record.entry_id = entry.id.clone();
+        dirty_files.push((key, record));
+        changed.push(entry);
     }
 
-    entries.sort_by(|a, b| {
-        a.group
-            .cmp(&b.group)
-            .then_with(|| a.title.cmp(&b.title))
-    });
+    // Parent manifests may have been added, edited or removed, so unchanged entries
+    // still need their group re-resolved.
+    let mut kept_ids = changed
+        .iter()
+        .map(|entry| entry.id.clone())
+        .collect::<HashSet<String>>();
+    for id in unchanged_ids {
+        let Some(mut entry) = existing.remove(&id) else {
+            continue;
+        };
+        kept_ids.insert(id);
+        let group = resolve_group_title(base, &entry, &parent_manifest_titles);
+        if group == entry.group {
+            summary.unchanged += 1;
+            continue;
+        }
+        entry.group = group;
+        summary.updated.push(entry.id.clone());
+        changed.push(entry);
+    }
+
+    summary.removed = existing_paths
+        .iter()
+        .filter(|(id, path)| !kept_ids.contains(*id) && in_scope(path))
+        .map(|(id, _)| id.clone())
+        .collect();
+    summary.added.sort();
+    summary.updated.sort();
+    summary.removed.sort();
+
+    // Assign every video file to the closest entry folder above it.
+    let mut entry_dirs = existing_paths
+        .iter()
+        .filter(|(id, _)| kept_ids.contains(*id))
+        .map(|(id, path)| (path.clone(), id.clone()))
+        .collect::<HashMap<String, String>>();
+    entry_dirs.extend(changed.iter().map(|entry| (entry.path.clone(), entry.id.clone())));
+
+    let known_episodes = episodes::load_episode_records(&conn, &library_root)?;
+    let mut episode_changes = HashSet::<String>::new();
+    let mut indexed_videos = HashSet::<String>::new();
+    let mut dirty_episodes = Vec::<(String, &Path, String, i64, i64)>::new();
+    for (path, modified_at, size) in &videos {
+        let Some(entry_id) = episodes::owning_entry(base, path, &entry_dirs) else {
+            continue;
+        };
+        let key = normalize_path(path);
+        indexed_videos.insert(key.clone());
+        let unchanged = known_episodes
+            .get(&key)
+            .map(|record| {
+                record.entry_id == *entry_id && record.modified_at == *modified_at && record.size == *size
+            })
+            .unwrap_or(false);
+        if unchanged {
+            continue;
+        }
+        episode_changes.insert(entry_id.clone());
+        dirty_episodes.push((key, path.as_path(), entry_id.clone(), *modified_at, *size));
+    }
+    let mut stale_episodes = Vec::<&str>::new();
+    for (path, record) in &known_episodes {
+        if in_scope(path) && !indexed_videos.contains(path) {
+            episode_changes.insert(record.entry_id.clone());
+            stale_episodes.push(path);
+        }
+    }
+    summary.episodes_changed = episode_changes
+        .into_iter()
+        .filter(|id| kept_ids.contains(id))
+        .collect();
+    summary.episodes_changed.sort();
 
+    // Last point a cancelled scan can stop; past it the results are written either way.
+    if let Some(reporter) = reporter.as_deref() {
+        reporter.begin_write()?;
+    }
     let now = Utc::now().timestamp();
     {
         let tx = conn
             .transaction()
             .map_err(|err| format!("Failed to start transaction: {}", err))?;
-        tx.execute(
-            "DELETE FROM manifest_entries WHERE library_root = ?",
-            [library_root.as_str()],
-        )
-        .map_err(|err| format!("Failed to clear entries: {}", err))?;
 
         {
             let mut stmt = tx
@@ -392,13 +1149,64 @@ fn refresh_library(base_dir: String) -> Result<Vec<LibraryEntry>, String> {
                         folder_name,
                         group_name,
                         relative_dir,
-                        updated_at
-                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                        updated_at,
+                        added_at,
+                        title_sort_key,
+                        group_sort_key,
+                        title_search_key,
+                        aliases,
+                        original_title,
+                        year,
+                        season,
+                        studio,
+                        tags,
+                        source,
+                        rating,
+                        external_ids,
+                        uid
+                    ) VALUES (
+                        ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?13, ?14, ?15, ?16,
+                        ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26
+                    )
+                    ON CONFLICT(id) DO UPDATE SET
+                        library_root = excluded.library_root,
+                        title = excluded.title,
+                        fansub = excluded.fansub,
+                        subtitle_type = excluded.subtitle_type,
+                        episodes = excluded.episodes,
+                        quality = excluded.quality,
+                        note = excluded.note,
+                        path = excluded.path,
+                        folder_name = excluded.folder_name,
+                        group_name = excluded.group_name,
+                        relative_dir = excluded.relative_dir,
+                        updated_at = excluded.updated_at,
+                        title_sort_key = excluded.title_sort_key,
+                        group_sort_key = excluded.group_sort_key,
+                        title_search_key = excluded.title_search_key,
+                        aliases = excluded.aliases,
+                        original_title = excluded.original_title,
+                        year = excluded.year,
+                        season = excluded.season,
+                        studio = excluded.studio,
+                        tags = excluded.tags,
+                        source = excluded.source,
+                        rating = excluded.rating,
+                        external_ids = excluded.external_ids,
+                        uid = excluded.uid
                     ",
                 )
                 .map_err(|err| format!("Failed to prepare insert: {}", err))?;
 
-            for entry in &entries {
+            for entry in &changed {
+                let keys = collation::title_keys(&entry.title);
+                let metadata = &entry.metadata;
+                let aliases = serde_json::to_string(&metadata.aliases)
+                    .map_err(|err| format!("Failed to encode aliases: {}", err))?;
+                let tags = serde_json::to_string(&metadata.tags)
+                    .map_err(|err| format!("Failed to encode tags: {}", err))?;
+                let external_ids = serde_json::to_string(&metadata.external_ids)
+                    .map_err(|err| format!("Failed to encode external ids: {}", err))?;
                 stmt.execute(params![
                     entry.id.as_str(),
                     library_root.as_str(),
@@ -412,93 +1220,239 @@ fn refresh_library(base_dir: String) -> Result<Vec<LibraryEntry>, String> {
                     entry.folder_name.as_str(),
                     entry.group.as_str(),
                     entry.relative_dir.as_str(),
-                    now
+                    now,
+                    keys.sort_key,
+                    collation::sort_key(&entry.group),
+                    keys.search_key,
+                    aliases,
+                    metadata.original_title.as_str(),
+                    metadata.year,
+                    metadata.season.as_str(),
+                    metadata.studio.as_str(),
+                    tags,
+                    metadata.source.as_str(),
+                    metadata.rating,
+                    external_ids,
+                    entry.uid.as_str()
                 ])
                 .map_err(|err| format!("Failed to insert entry: {}", err))?;
             }
         }
 
+        for path in known_files.keys().filter(|path| !seen_files.contains(*path)) {
+            tx.execute("DELETE FROM manifest_files WHERE path = ?", [path.as_str()])
+                .map_err(|err| format!("Failed to remove manifest record: {}", err))?;
+        }
+
+        {
+            let mut stmt = tx
+                .prepare(
+                    "
+                    INSERT INTO manifest_files (
+                        path,
+                        library_root,
+                        entry_id,
+                        is_parent,
+                        parent_title,
+                        modified_at,
+                        size,
+                        content_hash,
+                        scanned_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                    ON CONFLICT(path) DO UPDATE SET
+                        library_root = excluded.library_root,
+                        entry_id = excluded.entry_id,
+                        is_parent = excluded.is_parent,
+                        parent_title = excluded.parent_title,
+                        modified_at = excluded.modified_at,
+                        size = excluded.size,
+                        content_hash = excluded.content_hash,
+                        scanned_at = excluded.scanned_at
+                    ",
+                )
+                .map_err(|err| format!("Failed to prepare manifest record: {}", err))?;
+
+            for (path, record) in &dirty_files {
+                stmt.execute(params![
+                    path.as_str(),
+                    library_root.as_str(),
+                    record.entry_id.as_str(),
+                    record.is_parent,
+                    record.parent_title.as_str(),
+                    record.modified_at,
+                    record.size,
+                    record.content_hash.as_str(),
+                    now
+                ])
+                .map_err(|err| format!("Failed to save manifest record: {}", err))?;
+            }
+        }
+
+        for path in &stale_episodes {
+            tx.execute("DELETE FROM episode_files WHERE path = ?", [path])
+                .map_err(|err| format!("Failed to remove episode: {}", err))?;
+        }
+
+        {
+            let mut stmt = tx
+                .prepare(
+                    "
+                    INSERT INTO episode_files (
+                        path,
+                        entry_id,
+                        library_root,
+                        file_name,
+                        relative_path,
+                        episode_number,
+                        season,
+                        kind,
+                        size,
+                        modified_at,
+                        updated_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                    ON CONFLICT(path) DO UPDATE SET
+                        entry_id = excluded.entry_id,
+                        library_root = excluded.library_root,
+                        file_name = excluded.file_name,
+                        relative_path = excluded.relative_path,
+                        episode_number = excluded.episode_number,
+                        season = excluded.season,
+                        kind = excluded.kind,
+                        size = excluded.size,
+                        modified_at = excluded.modified_at,
+                        updated_at = excluded.updated_at
+                    ",
+                )
+                .map_err(|err| format!("Failed to prepare episode: {}", err))?;
+
+            for (key, path, entry_id, modified_at, size) in &dirty_episodes {
+                let file_name = path
+                    .file_name()
+                    .map(|value| value.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let relative_path = path
+                    .strip_prefix(base)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let parsed = parse_episode(&file_name);
+                stmt.execute(params![
+                    key.as_str(),
+                    entry_id.as_str(),
+                    library_root.as_str(),
+                    file_name.as_str(),
+                    relative_path.as_str(),
+                    parsed.number,
+                    parsed.season,
+                    parsed.kind.as_db(),
+                    size,
+                    modified_at,
+                    now
+                ])
+                .map_err(|err| format!("Failed to save episode: {}", err))?;
+            }
+        }
+
+        identity::update_fingerprints(
+            &tx,
+            summary
+                .added
+                .iter()
+                .chain(summary.episodes_changed.iter())
+                .filter(|id| kept_ids.contains(*id)),
+        )?;
+        let added_paths = changed
+            .iter()
+            .map(|entry| (entry.id.clone(), entry.path.clone()))
+            .collect::<HashMap<String, String>>();
+        summary.moved = identity::reconcile(
+            &tx,
+            &identity::candidates(&summary.removed, &existing_paths),
+            &identity::candidates(&summary.added, &added_paths),
+        )?;
+
+        for id in &summary.removed {
+            tx.execute("DELETE FROM manifest_entries WHERE id = ?", [id.as_str()])
+                .map_err(|err| format!("Failed to remove entry: {}", err))?;
+        }
+
+        tx.execute(
+            "DELETE FROM scan_diagnostics WHERE library_root = ?",
+            [library_root.as_str()],
+        )
+        .map_err(|err| format!("Failed to clear diagnostics: {}", err))?;
+
+        {
+            let mut stmt = tx
+                .prepare(
+                    "
+                    INSERT INTO scan_diagnostics (
+                        library_root,
+                        path,
+                        kind,
+                        message,
+                        line,
+                        column,
+                        detected_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    ",
+                )
+                .map_err(|err| format!("Failed to prepare diagnostic: {}", err))?;
+
+            for diagnostic in &diagnostics {
+                stmt.execute(params![
+                    library_root.as_str(),
+                    diagnostic.path.as_str(),
+                    diagnostic.kind.as_db(),
+                    diagnostic.message.as_str(),
+                    diagnostic.line,
+                    diagnostic.column,
+                    now
+                ])
+                .map_err(|err| format!("Failed to save diagnostic: {}", err))?;
+            }
+        }
+
         tx.commit()
             .map_err(|err| format!("Failed to commit transaction: {}", err))?;
     }
+    for moved in &summary.moved {
+        summary.added.retain(|id| *id != moved.to);
+        summary.removed.retain(|id| *id != moved.from);
+    }
 
-    load_entries(&conn, &library_root)
+    Ok(RefreshResult {
+        entries: load_entries(&conn, &library_root)?,
+        summary,
+        diagnostics,
+        skipped,
+    })
 }
 
 #[tauri::command]
-fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
+fn list_directory(app: tauri::AppHandle, path: String) -> Result<Vec<FileEntry>, String> {
     let target = PathBuf::from(path);
-    let mut entries = Vec::new();
-    let dir_entries = fs::read_dir(&target)
-        .map_err(|err| format!("Failed to read directory {}: {}", target.display(), err))?;
-
-    for entry in dir_entries {
-        let entry = entry.map_err(|err| format!("Failed to read entry: {}", err))?;
-        let file_name = entry.file_name().to_string_lossy().to_string();
-        if file_name.eq_ignore_ascii_case("manifest.yml") {
-            continue;
-        }
-        let metadata = entry
-            .metadata()
-            .map_err(|err| format!("Failed to read metadata: {}", err))?;
-        let file_type = metadata.is_dir();
-        let modified_at = metadata
-            .modified()
-            .ok()
-            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|value| value.as_secs() as i64)
-            .unwrap_or(0);
-        let mut size = if metadata.is_file() { metadata.len() } else { 0 };
-        let extension = entry
-            .path()
-            .extension()
-            .map(|value| value.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-        let mut has_manifest = false;
-        let mut manifest_title = String::new();
-        let mut manifest_fansub = String::new();
-        let mut manifest_subtitle_type = String::new();
-        let mut manifest_episodes = 0;
-        let mut manifest_quality = String::new();
-        let mut manifest_note = String::new();
-
-        if file_type {
-            let manifest_path = entry.path().join("manifest.yml");
-            if manifest_path.exists() {
-                if let Ok(content) = fs::read_to_string(&manifest_path) {
-                    if let Ok(raw) = serde_yaml::from_str::<ManifestRaw>(&content) {
-                        has_manifest = true;
-                        manifest_title = normalize(raw.title);
-                        manifest_fansub = normalize(raw.fansub);
-                        manifest_subtitle_type = normalize(raw.subtitle_type);
-                        manifest_episodes = normalize_episodes(raw.episodes);
-                        manifest_quality = normalize(raw.quality);
-                        manifest_note = normalize(raw.note);
-                    }
-                }
-            }
-        }
-
-        if file_type && has_manifest {
-            size = dir_size(&entry.path());
-        }
-
-        entries.push(FileEntry {
-            name: file_name,
-            path: normalize_path(&entry.path()),
-            is_dir: file_type,
-            size,
-            modified_at,
-            extension,
-            has_manifest,
-            manifest_title,
-            manifest_fansub,
-            manifest_subtitle_type,
-            manifest_episodes,
-            manifest_quality,
-            manifest_note,
-        });
+    let settings = libraries::scan_settings(&app, &target);
+    if settings.ignore.explain(&target).is_some() {
+        return Ok(Vec::new());
     }
+    read_directory(&target, &settings)
+}
+
+/// Lists the children of `target`, folders first, with each folder's total size.
+fn read_directory(target: &Path, settings: &libraries::ScanSettings) -> Result<Vec<FileEntry>, String> {
+    let dir_entries = fs::read_dir(target)
+        .map_err(|err| format!("Failed to read directory {}: {}", target.display(), err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Failed to read entry: {}", err))?;
+
+    // Sizing a title folder walks its whole tree, so children are read side by side.
+    let mut entries = parallel::map_ordered(&dir_entries, settings.concurrency, |entry| {
+        read_file_entry(entry, settings)
+    })
+        .into_iter()
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>, _>>()?;
 
     entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
         (true, false) => Ordering::Less,
@@ -509,9 +1463,84 @@ fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
     Ok(entries)
 }
 
-fn dir_size(path: &Path) -> u64 {
-    WalkDir::new(path)
+fn read_file_entry(
+    entry: &fs::DirEntry,
+    settings: &libraries::ScanSettings,
+) -> Result<Option<FileEntry>, String> {
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    if settings.manifest_names.is_manifest(&entry.path()) || file_name == ignore::IGNORE_FILE_NAME {
+        return Ok(None);
+    }
+    let metadata = entry
+        .metadata()
+        .map_err(|err| format!("Failed to read metadata: {}", err))?;
+    if settings.ignore.check(&entry.path(), metadata.is_dir()).is_some() {
+        return Ok(None);
+    }
+    let file_type = metadata.is_dir();
+    let modified_at = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|value| value.as_secs() as i64)
+        .unwrap_or(0);
+    let mut size = if metadata.is_file() { metadata.len() } else { 0 };
+    let extension = entry
+        .path()
+        .extension()
+        .map(|value| value.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut has_manifest = false;
+    let mut manifest_title = String::new();
+    let mut manifest_fansub = String::new();
+    let mut manifest_subtitle_type = String::new();
+    let mut manifest_episodes = 0;
+    let mut manifest_quality = String::new();
+    let mut manifest_note = String::new();
+
+    if file_type {
+        if let Some(manifest_path) = settings.manifest_names.find(&entry.path()) {
+            if let Ok(content) = fs::read_to_string(&manifest_path) {
+                let format = ManifestFormat::from_path(&manifest_path);
+                if let Ok(raw) = manifest::parse_manifest(&content, format) {
+                    has_manifest = true;
+                    manifest_title = normalize(raw.title);
+                    manifest_fansub = normalize(raw.fansub);
+                    manifest_subtitle_type = normalize(raw.subtitle_type);
+                    manifest_episodes = normalize_episodes(raw.episodes);
+                    manifest_quality = normalize(raw.quality);
+                    manifest_note = normalize(raw.note);
+                }
+            }
+        }
+    }
+
+    if file_type && has_manifest {
+        size = dir_size(&entry.path(), settings);
+    }
+
+    Ok(Some(FileEntry {
+        name: file_name,
+        path: normalize_path(&entry.path()),
+        is_dir: file_type,
+        size,
+        modified_at,
+        extension,
+        has_manifest,
+        manifest_title,
+        manifest_fansub,
+        manifest_subtitle_type,
+        manifest_episodes,
+        manifest_quality,
+        manifest_note,
+    }))
+}
+
+fn dir_size(path: &Path, settings: &libraries::ScanSettings) -> u64 {
+    settings
+        .walk_dir(path)
         .into_iter()
+        .filter_entry(|entry| settings.ignore.check(entry.path(), entry.file_type().is_dir()).is_none())
         .filter_map(Result::ok)
         .filter(|entry| entry.file_type().is_file())
         .filter_map(|entry| entry.metadata().ok().map(|meta| meta.len()))
@@ -552,20 +1581,21 @@ fn build_video_index_markdown(entries: &[LibraryEntry]) -> String {
     }
 
     let mut group_names: Vec<String> = groups.keys().cloned().collect();
-    group_names.sort();
+    group_names.sort_by_cached_key(|name| (collation::sort_key(name), name.clone()));
     for name in &group_names {
         if let Some(items) = groups.get_mut(name) {
-            items.sort_by(|a, b| a.title.cmp(&b.title));
+            items.sort_by_cached_key(|item| (collation::sort_key(&item.title), item.title.clone()));
         }
     }
 
-    let mut lines = Vec::<String>::new();
-    lines.push("# 视频信息".to_string());
-    lines.push(String::new());
-    lines.push("## 目录".to_string());
-    lines.push(String::new());
-    lines.push("- [视频信息](#视频信息)".to_string());
-    lines.push("  - [目录](#目录)".to_string());
+    let mut lines = vec![
+        "# 视频信息".to_string(),
+        String::new(),
+        "## 目录".to_string(),
+        String::new(),
+        "- [视频信息](#视频信息)".to_string(),
+        "  - [目录](#目录)".to_string(),
+    ];
     for group in &group_names {
         lines.push(format!("    - [{}](#{})", group, markdown_anchor(group)));
         if let Some(items) = groups.get(group) {
@@ -620,13 +1650,29 @@ fn build_video_index_markdown(entries: &[LibraryEntry]) -> String {
     lines.join("\n")
 }
 
-#[tauri::command]
-fn create_anime_manifest(base_dir: String, payload: NewAnimePayload) -> Result<Option<String>, String> {
-    let base = PathBuf::from(base_dir.trim());
-    if !base.exists() {
-        return Err(format!("Base directory not found: {}", base.display()));
+/// Writes the form fields over `document`, keeping keys the form does not edit.
+fn merge_manifest(
+    mut document: serde_yaml::Mapping,
+    fields: ManifestWriteModel,
+    metadata: MetadataUpdate,
+) -> Result<serde_yaml::Mapping, String> {
+    let fields = serde_yaml::to_value(&fields).map_err(|err| format!("Failed to build manifest content: {}", err))?;
+    if let serde_yaml::Value::Mapping(fields) = fields {
+        for (key, value) in fields {
+            document.insert(key, value);
+        }
+    }
+    metadata.apply(&mut document);
+    // A stable id lets the index follow the folder when it is renamed or moved.
+    if !document.contains_key("id") {
+        document.insert("id".into(), uuid::Uuid::new_v4().to_string().into());
     }
+    Ok(document)
+}
 
+/// Checks the title and episode count of a manifest form and splits it into the fields
+/// it writes and the optional metadata.
+fn checked_fields(payload: NewAnimePayload) -> Result<(ManifestWriteModel, MetadataUpdate), String> {
     let title = payload.title.trim().to_string();
     if title.is_empty() {
         return Err("动画名称不能为空".to_string());
@@ -641,6 +1687,37 @@ fn create_anime_manifest(base_dir: String, payload: NewAnimePayload) -> Result<O
         -1
     };
 
+    let fields = ManifestWriteModel {
+        schema_version: manifest::CURRENT_SCHEMA_VERSION,
+        title,
+        fansub: normalize_new_text(payload.fansub),
+        subtitle_type: normalize_new_text(payload.subtitle_type),
+        episodes,
+        quality: normalize_new_text(payload.quality),
+        note: normalize_new_text(payload.note),
+    };
+    Ok((fields, payload.metadata))
+}
+
+/// Checks a create-form payload and builds the manifest document for it.
+fn new_manifest_document(payload: NewAnimePayload) -> Result<serde_yaml::Mapping, String> {
+    let (fields, metadata) = checked_fields(payload)?;
+    merge_manifest(serde_yaml::Mapping::new(), fields, metadata)
+}
+
+#[tauri::command]
+fn create_anime_manifest(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    payload: NewAnimePayload,
+) -> Result<Option<String>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+
+    let document = new_manifest_document(payload)?;
+
     let selected_path = rfd::FileDialog::new()
         .set_title("保存 manifest.yml")
         .set_directory(&base)
@@ -654,77 +1731,92 @@ fn create_anime_manifest(base_dir: String, payload: NewAnimePayload) -> Result<O
             .map_err(|err| format!("Failed to create folder {}: {}", parent.display(), err))?;
     }
 
-    let content = serde_yaml::to_string(&ManifestWriteModel {
-        title,
-        is_parent: None,
-        fansub: normalize_new_text(payload.fansub),
-        subtitle_type: normalize_new_text(payload.subtitle_type),
-        episodes,
-        quality: normalize_new_text(payload.quality),
-        note: normalize_new_text(payload.note),
-    })
-    .map_err(|err| format!("Failed to build manifest content: {}", err))?;
-
-    fs::write(&manifest_path, content)
-        .map_err(|err| format!("Failed to write {}: {}", manifest_path.display(), err))?;
+    let content = manifest::render(&document, ManifestFormat::from_path(&manifest_path))?;
+    history::save_manifest(&database, &base, &manifest_path, &content)?;
 
     Ok(Some(normalize_path(&manifest_path)))
 }
 
+/// Edits a manifest in place, rewriting only the fields that changed so comments,
+/// key order and keys the app does not know survive.
+///
+/// `expected_revision` is the `manifestRevision` the form was loaded from; the edit is
+/// refused when the file has been changed on disk since.
 #[tauri::command]
-fn update_anime_manifest(entry_path: String, payload: NewAnimePayload) -> Result<String, String> {
+fn update_anime_manifest(
+    app: tauri::AppHandle,
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_path: String,
+    payload: NewAnimePayload,
+    expected_revision: Option<String>,
+) -> Result<String, String> {
+    let base = PathBuf::from(base_dir.trim());
     let target_dir = PathBuf::from(entry_path.trim());
     if !target_dir.exists() {
         return Err(format!("Entry directory not found: {}", target_dir.display()));
     }
 
-    let title = payload.title.trim().to_string();
-    if title.is_empty() {
-        return Err("动画名称不能为空".to_string());
+    let (fields, metadata) = checked_fields(payload)?;
+
+    // Edit whichever manifest the folder already has, so `manifest.yaml` isn't shadowed.
+    let manifest_path = libraries::scan_settings(&app, &target_dir)
+        .manifest_names
+        .find(&target_dir)
+        .unwrap_or_else(|| target_dir.join(MANIFEST_FILE_NAME));
+    let format = ManifestFormat::from_path(&manifest_path);
+    let current = fs::read(&manifest_path).ok();
+    if let Some(expected) = expected_revision.filter(|value| !value.is_empty()) {
+        let actual = current.as_deref().map(hash_content).unwrap_or_default();
+        if actual != expected {
+            return Err(format!(
+                "{} 在加载后已被修改，请刷新后再编辑",
+                manifest_path.display()
+            ));
+        }
+    }
+    let current = current.and_then(|content| String::from_utf8(content).ok());
+    let existing = current
+        .as_deref()
+        .and_then(|content| manifest::load_document(content, format))
+        .unwrap_or_default();
+
+    let document = merge_manifest(existing.clone(), fields, metadata)?;
+    if document == existing {
+        return Ok(normalize_path(&manifest_path));
     }
 
-    let episodes = if payload.is_finished {
-        if payload.episodes < 0 {
-            return Err("已完结动画的集数必须是非负整数（0 表示未知）".to_string());
-        }
-        payload.episodes
-    } else {
-        -1
+    // Layouts the patcher can't edit safely are rewritten whole, as are JSON and TOML
+    // manifests, in their own format and with their keys in the same order.
+    let patched = match format {
+        ManifestFormat::Yaml => current
+            .as_deref()
+            .and_then(|content| manifest_patch::patch_manifest(content, &existing, &document)),
+        _ => None,
     };
-
-    let manifest_path = target_dir.join("manifest.yml");
-    let existing_parent_flag = fs::read_to_string(&manifest_path)
-        .ok()
-        .and_then(|content| serde_yaml::from_str::<ManifestRaw>(&content).ok())
-        .map(|raw| raw.is_parent)
-        .unwrap_or(false);
-
-    let content = serde_yaml::to_string(&ManifestWriteModel {
-        title,
-        is_parent: if existing_parent_flag { Some(true) } else { None },
-        fansub: normalize_new_text(payload.fansub),
-        subtitle_type: normalize_new_text(payload.subtitle_type),
-        episodes,
-        quality: normalize_new_text(payload.quality),
-        note: normalize_new_text(payload.note),
-    })
-    .map_err(|err| format!("Failed to build manifest content: {}", err))?;
-
-    fs::write(&manifest_path, content)
-        .map_err(|err| format!("Failed to write {}: {}", manifest_path.display(), err))?;
+    let content = match patched {
+        Some(content) => content,
+        None => manifest::render(&document, format)?,
+    };
+    history::save_manifest(&database, &base, &manifest_path, &content)?;
 
     Ok(normalize_path(&manifest_path))
 }
 
 #[tauri::command]
-fn generate_video_index_markdown(base_dir: String) -> Result<String, String> {
+fn generate_video_index_markdown(
+    app: tauri::AppHandle,
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+) -> Result<String, String> {
     let base = PathBuf::from(base_dir.trim());
     if !base.exists() {
         return Err(format!("Base directory not found: {}", base.display()));
     }
 
-    let entries = refresh_library(base_dir)?;
-    let markdown = build_video_index_markdown(&entries);
+    let settings = libraries::scan_settings(&app, &base);
+    let result = refresh_index(&database, &base, &[], false, &settings, None)?;
+    let markdown = build_video_index_markdown(&result.entries);
     let output_path = base.join("视频索引.MD");
     fs::write(&output_path, markdown)
         .map_err(|err| format!("Failed to write {}: {}", output_path.display(), err))?;
@@ -732,9 +1824,15 @@ fn generate_video_index_markdown(base_dir: String) -> Result<String, String> {
 }
 
 #[tauri::command]
-fn update_play_history(base_dir: String, entry_id: String, file_path: String, file_name: String) -> Result<(), String> {
+fn update_play_history(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+    file_path: String,
+    file_name: String,
+) -> Result<(), String> {
     let base = PathBuf::from(base_dir.trim());
-    let conn = open_db(&base)?;
+    let conn = database.open(&base)?;
     let now = Utc::now().timestamp();
     conn.execute(
         "
@@ -775,14 +1873,51 @@ fn show_error_dialog(title: String, message: String) -> Result<(), String> {
 
 fn main() {
     tauri::Builder::default()
+        .manage(watcher::LibraryWatcher::default())
+        .manage(scan::ScanManager::default())
+        .setup(|app| {
+            storage::init(app.handle())?;
+            // A broken registry shouldn't keep the app from starting; it is reported when listed.
+            let _ = app.state::<watcher::LibraryWatcher>().sync(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             load_library,
-            refresh_library,
+            scan::refresh_library,
+            scan::cancel_scan,
             list_directory,
             create_anime_manifest,
             update_anime_manifest,
             generate_video_index_markdown,
             update_play_history,
+            episodes::list_episodes,
+            integrity::check_entry_integrity,
+            integrity::check_library_integrity,
+            ignore::explain_ignored,
+            libraries::list_libraries,
+            libraries::add_library,
+            libraries::update_library,
+            libraries::remove_library,
+            storage::get_database_location,
+            storage::set_database_location,
+            storage::has_legacy_database,
+            storage::migrate_legacy_database,
+            storage::remove_legacy_database,
+            search::search_library,
+            query::query_library,
+            manifest::validate_manifests,
+            manifest::migrate_manifests,
+            manifest::convert_manifest,
+            discover::discover_manifests,
+            discover::write_discovered_manifests,
+            history::list_manifest_history,
+            history::revert_manifest,
+            progress::mark_episode_watched,
+            progress::mark_episodes_watched_through,
+            progress::reset_watch_progress,
+            progress::update_playback_position,
+            watcher::watch_library,
+            watcher::unwatch_library,
             open_in_explorer,
             open_path,
             show_error_dialog
@@ -790,3 +1925,392 @@ fn main() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    struct Library {
+        root: tempfile::TempDir,
+        // Kept apart from the root so the walk never sees the database files.
+        _data: tempfile::TempDir,
+        database: Database,
+    }
+
+    impl Library {
+        fn new() -> Self {
+            let data = tempfile::tempdir().unwrap();
+            let database = Database::new(data.path().join("index.sqlite"));
+            Self {
+                root: tempfile::tempdir().unwrap(),
+                _data: data,
+                database,
+            }
+        }
+
+        fn dir(&self, name: &str) -> PathBuf {
+            self.root.path().join(name)
+        }
+
+        /// Writes `content` as the manifest of folder `name`, dated `age` seconds into the past
+        /// so a later write always changes the stat.
+        fn write(&self, name: &str, content: &str, age: u64) {
+            let dir = self.dir(name);
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(MANIFEST_FILE_NAME);
+            fs::write(&path, content).unwrap();
+            set_modified(&path, age);
+        }
+
+        fn refresh(&self, scope: &[PathBuf]) -> RefreshResult {
+            self.refresh_with(scope, &libraries::ScanSettings::defaults(self.root.path()))
+        }
+
+        fn refresh_with(&self, scope: &[PathBuf], settings: &libraries::ScanSettings) -> RefreshResult {
+            refresh_index(&self.database, self.root.path(), scope, false, settings, None).unwrap()
+        }
+
+        fn settings(&self, edit: impl FnOnce(&mut libraries::ScanSettings)) -> libraries::ScanSettings {
+            let mut settings = libraries::ScanSettings::defaults(self.root.path());
+            edit(&mut settings);
+            settings
+        }
+    }
+
+    fn set_modified(path: &Path, age: u64) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+    }
+
+    fn id_of(result: &RefreshResult, title: &str) -> String {
+        result
+            .entries
+            .iter()
+            .find(|entry| entry.title == title)
+            .map(|entry| entry.id.clone())
+            .unwrap_or_else(|| panic!("{} is not indexed", title))
+    }
+
+    fn titles(result: &RefreshResult) -> Vec<&str> {
+        let mut titles = result.entries.iter().map(|entry| entry.title.as_str()).collect::<Vec<_>>();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn refresh_reports_added_updated_touched_and_removed_entries() {
+        let library = Library::new();
+        library.write("A", "title: Frieren\n", 100);
+        library.write("B", "title: Mushishi\n", 100);
+        library.write("C", "title: Haibane Renmei\n", 100);
+
+        let first = library.refresh(&[]);
+        assert_eq!(first.summary.added.len(), 3);
+        assert!(first.summary.updated.is_empty() && first.summary.removed.is_empty());
+        let a = id_of(&first, "Frieren");
+        let c = id_of(&first, "Haibane Renmei");
+
+        library.write("A", "title: Sousou no Frieren\n", 50);
+        // Touched without an edit: the stat changes, the content hash does not.
+        set_modified(&library.dir("B").join(MANIFEST_FILE_NAME), 50);
+        fs::remove_dir_all(library.dir("C")).unwrap();
+
+        let second = library.refresh(&[]);
+        assert!(second.summary.added.is_empty());
+        assert_eq!(second.summary.updated, std::slice::from_ref(&a));
+        assert_eq!(second.summary.removed, [c]);
+        assert_eq!(second.summary.unchanged, 1);
+        assert_eq!(titles(&second), ["Mushishi", "Sousou no Frieren"]);
+        assert_eq!(id_of(&second, "Sousou no Frieren"), a);
+
+        let third = library.refresh(&[]);
+        assert!(third.summary.added.is_empty() && third.summary.updated.is_empty());
+        assert!(third.summary.removed.is_empty());
+        assert_eq!(third.summary.unchanged, 2);
+    }
+
+    #[test]
+    fn scoped_refresh_leaves_folders_outside_the_scope_alone() {
+        let library = Library::new();
+        library.write("A", "title: Frieren\n", 100);
+        library.write("B", "title: Mushishi\n", 100);
+        library.refresh(&[]);
+
+        library.write("A", "title: Sousou no Frieren\n", 50);
+        fs::remove_dir_all(library.dir("B")).unwrap();
+        library.write("C", "title: Haibane Renmei\n", 50);
+
+        let scoped = library.refresh(&[library.dir("A")]);
+        assert_eq!(scoped.summary.updated.len(), 1);
+        assert!(scoped.summary.added.is_empty() && scoped.summary.removed.is_empty());
+        assert_eq!(titles(&scoped), ["Mushishi", "Sousou no Frieren"]);
+
+        let full = library.refresh(&[]);
+        assert_eq!(full.summary.added.len(), 1);
+        assert_eq!(full.summary.removed.len(), 1);
+        assert_eq!(titles(&full), ["Haibane Renmei", "Sousou no Frieren"]);
+    }
+
+    /// `list_directory` over a library root, which sizes every title folder, and a full
+    /// refresh into an empty index, each on one thread and on the default worker count.
+    /// Run with `cargo test --release bench_listing_and_refresh -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_listing_and_refresh() {
+        let library = Library::new();
+        for index in 0..2_000 {
+            let name = format!("[Group] Title {:04} [01-12][1080p]", index);
+            library.write(&name, &format!("title: Title {}\nepisodes: 12\n", index), 100);
+            for episode in 1..=12 {
+                let video = format!("[Group] Title {:04} - {:02} [1080p].mkv", index, episode);
+                fs::write(library.dir(&name).join(video), [0u8; 1024]).unwrap();
+            }
+        }
+
+        let time = |run: &dyn Fn()| {
+            let started = std::time::Instant::now();
+            run();
+            started.elapsed()
+        };
+        let listing = |concurrency: usize| {
+            let mut settings = libraries::ScanSettings::defaults(library.root.path());
+            settings.concurrency = concurrency;
+            time(&|| assert_eq!(read_directory(library.root.path(), &settings).unwrap().len(), 2_000))
+        };
+        let refresh = |concurrency: usize| {
+            let mut settings = libraries::ScanSettings::defaults(library.root.path());
+            settings.concurrency = concurrency;
+            let data = tempfile::tempdir().unwrap();
+            let database = Database::new(data.path().join("index.sqlite"));
+            time(&|| {
+                let base = library.root.path();
+                let result = refresh_index(&database, base, &[], true, &settings, None).unwrap();
+                assert_eq!(result.summary.added.len(), 2_000);
+            })
+        };
+
+        // The first pass warms the page cache so every timing reads the same way.
+        listing(parallel::default_concurrency());
+        let threads = parallel::default_concurrency();
+        let stages: [(&str, &dyn Fn(usize) -> Duration); 2] = [("list_directory", &listing), ("refresh_index", &refresh)];
+        for (stage, run) in stages {
+            let serial = run(1);
+            let parallel = run(threads);
+            println!(
+                "{} over 2k folders: 1 thread {:?}, {} threads {:?} ({:.2}x)",
+                stage,
+                serial,
+                threads,
+                parallel,
+                serial.as_secs_f64() / parallel.as_secs_f64()
+            );
+        }
+    }
+
+    #[test]
+    fn cancelled_scan_leaves_the_index_unchanged() {
+        let library = Library::new();
+        library.write("A", "title: Frieren\n", 100);
+        library.refresh(&[]);
+        library.write("A", "title: Sousou no Frieren\n", 50);
+        library.write("B", "title: Mushishi\n", 50);
+
+        let control = std::sync::Arc::new(scan::ScanControl::default());
+        let base_dir = normalize_path(library.root.path());
+        let mut reporter = scan::ScanReporter::new("scan-1".to_string(), base_dir.clone(), control.clone(), |_| {});
+        assert!(control.cancel().unwrap());
+        let base = library.root.path();
+        let settings = libraries::ScanSettings::defaults(base);
+        let result = refresh_index(&library.database, base, &[], false, &settings, Some(&mut reporter));
+        assert_eq!(result.err().as_deref(), Some("扫描已取消"));
+
+        let conn = library.database.connection().unwrap();
+        let entries = load_entries(&conn, &base_dir).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.title.as_str()).collect::<Vec<_>>(), ["Frieren"]);
+    }
+
+    #[test]
+    fn broken_manifest_is_reported_and_the_others_stay_indexed() {
+        let library = Library::new();
+        library.write("A", "title: Frieren\n", 100);
+        library.write("B", "title: [unclosed\n", 100);
+
+        let first = library.refresh(&[]);
+        assert_eq!(titles(&first), ["Frieren"]);
+        let broken = normalize_path(&library.dir("B").join(MANIFEST_FILE_NAME));
+        assert!(first.diagnostics.iter().any(|diagnostic| diagnostic.path == broken));
+
+        // A manifest that breaks later keeps serving its last good version.
+        library.write("B", "title: Mushishi\n", 50);
+        assert_eq!(titles(&library.refresh(&[])), ["Frieren", "Mushishi"]);
+        library.write("B", "title: [unclosed again\n", 10);
+        let last = library.refresh(&[]);
+        assert_eq!(titles(&last), ["Frieren", "Mushishi"]);
+        assert!(last.summary.removed.is_empty());
+        assert!(last.diagnostics.iter().any(|diagnostic| diagnostic.path == broken));
+    }
+
+    fn diagnostics_of(result: &RefreshResult, kind: DiagnosticKind) -> Vec<String> {
+        let mut paths = result
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.kind == kind)
+            .map(|diagnostic| diagnostic.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn several_manifests_in_one_folder_keep_the_preferred_one() {
+        let library = Library::new();
+        let names = manifest::ManifestNames::default();
+        let a = library.dir("A");
+        let b = library.dir("B");
+        let paths = vec![
+            a.join("manifest.yaml"),
+            a.join("manifest.yml"),
+            a.join("Manifest.yml"),
+            b.join("manifest.json"),
+        ];
+        let mut diagnostics = Vec::new();
+        let picked = pick_manifests(paths, &names, &mut diagnostics);
+        assert_eq!(picked, [a.join("manifest.yml"), b.join("manifest.json")]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Conflict);
+        assert_eq!(diagnostics[0].path, normalize_path(&a));
+        assert!(diagnostics[0].message.ends_with("using manifest.yml"));
+    }
+
+    #[test]
+    fn conflicting_manifests_are_reported_and_the_preferred_one_is_indexed() {
+        let library = Library::new();
+        library.write("A", "title: From yml\n", 100);
+        fs::write(library.dir("A").join("manifest.json"), r#"{"title": "From json"}"#).unwrap();
+        fs::create_dir_all(library.dir("B")).unwrap();
+        fs::write(library.dir("B").join("Manifest.Toml"), "title = \"From toml\"\n").unwrap();
+
+        let result = library.refresh(&[]);
+        assert_eq!(titles(&result), ["From toml", "From yml"]);
+        let conflicts = result
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.kind == DiagnosticKind::Conflict)
+            .collect::<Vec<_>>();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, normalize_path(&library.dir("A")));
+        assert_eq!(
+            conflicts[0].message,
+            "Several manifests in one folder (manifest.yml, manifest.json); using manifest.yml"
+        );
+    }
+
+    #[test]
+    fn depth_limit_counts_from_the_library_root() {
+        let library = Library::new();
+        library.write("A", "title: Frieren\n", 100);
+        library.write("Group/Show", "title: Mushishi\n", 100);
+        let settings = library.settings(|settings| settings.max_depth = Some(2));
+
+        assert_eq!(titles(&library.refresh_with(&[], &settings)), ["Frieren"]);
+        // A rescan of a sub-folder doesn't get a fresh depth budget.
+        let scoped = library.refresh_with(&[library.dir("Group")], &settings);
+        assert_eq!(titles(&scoped), ["Frieren"]);
+        assert_eq!(titles(&library.refresh(&[])), ["Frieren", "Mushishi"]);
+    }
+
+    #[cfg(unix)]
+    mod links {
+        use super::*;
+        use std::os::unix::fs::{symlink, MetadataExt};
+
+        #[test]
+        fn links_are_reported_when_not_followed() {
+            let library = Library::new();
+            library.write("A", "title: Frieren\n", 100);
+            symlink(library.dir("A"), library.dir("Alias")).unwrap();
+
+            let result = library.refresh(&[]);
+            assert_eq!(titles(&result), ["Frieren"]);
+            assert_eq!(diagnostics_of(&result, DiagnosticKind::Link), [normalize_path(&library.dir("Alias"))]);
+        }
+
+        #[test]
+        fn followed_links_skip_loops_and_duplicate_targets() {
+            let library = Library::new();
+            library.write("A", "title: Frieren\n", 100);
+            library.write("Show", "title: Mushishi\n", 100);
+            symlink(library.dir("A"), library.dir("Alias")).unwrap();
+            symlink(library.dir("Show"), library.dir("Show").join("loop")).unwrap();
+            let settings = library.settings(|settings| settings.follow_symlinks = true);
+
+            let result = library.refresh_with(&[], &settings);
+            assert_eq!(titles(&result), ["Frieren", "Mushishi"]);
+            let alias = normalize_path(&library.dir("Alias"));
+            assert_eq!(diagnostics_of(&result, DiagnosticKind::Duplicate), std::slice::from_ref(&alias));
+            let loops = diagnostics_of(&result, DiagnosticKind::Link);
+            assert_eq!(loops, [normalize_path(&library.dir("Show").join("loop"))]);
+
+            // A rescan of just the link still sees the folder indexed under its real path.
+            let scoped = library.refresh_with(&[library.dir("Alias")], &settings);
+            assert_eq!(titles(&scoped), ["Frieren", "Mushishi"]);
+            assert!(scoped.summary.added.is_empty());
+            assert_eq!(diagnostics_of(&scoped, DiagnosticKind::Duplicate), [alias]);
+        }
+
+        #[test]
+        fn duplicate_dirs_prefers_the_canonical_path() {
+            let dir = tempfile::tempdir().unwrap();
+            let root = canonical_path(dir.path()).unwrap();
+            let real = root.join("Real");
+            fs::create_dir_all(&real).unwrap();
+            symlink(&real, root.join("a")).unwrap();
+            symlink(&real, root.join("bb")).unwrap();
+            let manifest = |dir: &str| root.join(dir).join(MANIFEST_FILE_NAME);
+
+            let mut diagnostics = Vec::new();
+            let all = [manifest("bb"), manifest("Real"), manifest("a")];
+            let mut duplicates = duplicate_dirs(&all, &HashSet::new(), 2, &mut diagnostics);
+            duplicates.sort();
+            assert_eq!(duplicates, [root.join("a"), root.join("bb")]);
+            assert_eq!(diagnostics.len(), 2);
+            assert!(diagnostics.iter().all(|diagnostic| diagnostic.kind == DiagnosticKind::Duplicate));
+
+            // Without the real path among them, the shortest link wins.
+            let mut diagnostics = Vec::new();
+            let links = [manifest("bb"), manifest("a")];
+            assert_eq!(duplicate_dirs(&links, &HashSet::new(), 1, &mut diagnostics), [root.join("bb")]);
+
+            // A lone link to a folder indexed outside the walked scope is a duplicate too.
+            let indexed = HashSet::from([real.clone()]);
+            let mut diagnostics = Vec::new();
+            assert_eq!(duplicate_dirs(&[manifest("a")], &indexed, 1, &mut diagnostics), [root.join("a")]);
+            assert!(duplicate_dirs(&[manifest("a")], &HashSet::new(), 1, &mut diagnostics).is_empty());
+        }
+
+        #[test]
+        fn same_file_system_stops_at_mount_boundaries() {
+            // Needs a writable folder on another file system; tmpfs at /dev/shm usually is one.
+            let Ok(other) = tempfile::tempdir_in("/dev/shm") else {
+                return;
+            };
+            let library = Library::new();
+            let device = |path: &Path| fs::metadata(path).unwrap().dev();
+            if device(other.path()) == device(library.root.path()) {
+                return;
+            }
+            library.write("A", "title: Frieren\n", 100);
+            fs::write(other.path().join(MANIFEST_FILE_NAME), "title: Mushishi\n").unwrap();
+            symlink(other.path(), library.dir("Mounted")).unwrap();
+
+            let crossing = library.settings(|settings| settings.follow_symlinks = true);
+            assert_eq!(titles(&library.refresh_with(&[], &crossing)), ["Frieren", "Mushishi"]);
+            let staying = library.settings(|settings| {
+                settings.follow_symlinks = true;
+                settings.same_file_system = true;
+            });
+            assert_eq!(titles(&library.refresh_with(&[], &staying)), ["Frieren"]);
+        }
+    }
+}
@@ -0,0 +1,1068 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::database::Database;
+use crate::{history, libraries, manifest_patch, normalize_path, ManifestRaw};
+
+/// Version written by this build. Manifests without `schema_version` are version 0.
+pub const CURRENT_SCHEMA_VERSION: u64 = 1;
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Keys understood by the current schema.
+const KNOWN_KEYS: &[&str] = &[
+    SCHEMA_VERSION_KEY,
+    "id",
+    "title",
+    "is_parent",
+    "fansub",
+    "subtitle_type",
+    "episodes",
+    "quality",
+    "note",
+    "aliases",
+    "original_title",
+    "year",
+    "season",
+    "studio",
+    "tags",
+    "source",
+    "rating",
+    "external_ids",
+];
+
+/// File names a manifest may have, most preferred first. Matched regardless of case.
+const MANIFEST_NAMES: &[&str] = &["manifest.yml", "manifest.yaml", "manifest.json", "manifest.toml"];
+
+/// Hidden names, accepted in libraries that opt in to them.
+const HIDDEN_MANIFEST_NAMES: &[&str] = &[".anime.yml", ".anime.yaml", ".anime.json", ".anime.toml"];
+
+/// Key the TOML deserializer wraps dates and times in.
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// Syntax a manifest is written in, told apart by its file extension. All of them share
+/// one schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ManifestFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().map(|value| value.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("json") => ManifestFormat::Json,
+            Some("toml") => ManifestFormat::Toml,
+            _ => ManifestFormat::Yaml,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ManifestFormat::Yaml => "yml",
+            ManifestFormat::Json => "json",
+            ManifestFormat::Toml => "toml",
+        }
+    }
+}
+
+/// A manifest that failed to parse, with the 1-based line and column the parser points
+/// at when it reports one.
+#[derive(Debug)]
+pub struct ManifestError {
+    message: String,
+    location: Option<(usize, usize)>,
+}
+
+impl ManifestError {
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    fn json(err: serde_json::Error) -> Self {
+        Self {
+            location: (err.line() > 0).then(|| (err.line(), err.column())),
+            message: err.to_string(),
+        }
+    }
+
+    /// TOML errors carry a byte span rather than a position; it is resolved against `content`.
+    fn toml(err: toml::de::Error, content: &str) -> Self {
+        let location = err.span().map(|span| {
+            let before = &content[..span.start.min(content.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+            (line, column)
+        });
+        let message = err.message().lines().collect::<Vec<_>>().join(", ");
+        Self {
+            message: match location {
+                Some((line, column)) => format!("{} at line {} column {}", message, line, column),
+                None => message,
+            },
+            location,
+        }
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<serde_yaml::Error> for ManifestError {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self {
+            location: err.location().map(|location| (location.line(), location.column())),
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Which file names count as a manifest in a library.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ManifestNames {
+    hidden: bool,
+}
+
+impl ManifestNames {
+    pub fn new(hidden: bool) -> Self {
+        Self { hidden }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> {
+        let hidden = if self.hidden { HIDDEN_MANIFEST_NAMES } else { &[] };
+        MANIFEST_NAMES.iter().chain(hidden).copied()
+    }
+
+    /// Preference of `path` among the manifests of one folder, lower first, or `None`
+    /// when its name isn't a manifest name. An exact-case match beats a case variant.
+    pub fn rank(&self, path: &Path) -> Option<usize> {
+        let name = path.file_name()?.to_string_lossy();
+        let index = self.names().position(|candidate| name.eq_ignore_ascii_case(candidate))?;
+        let exact = self.names().any(|candidate| name == candidate);
+        Some(index * 2 + usize::from(!exact))
+    }
+
+    pub fn is_manifest(&self, path: &Path) -> bool {
+        self.rank(path).is_some()
+    }
+
+    /// The manifest in `dir` the scanner would index, if any.
+    pub fn find(&self, dir: &Path) -> Option<PathBuf> {
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().map(|kind| kind.is_file()).unwrap_or(false))
+            .filter_map(|entry| self.rank(&entry.path()).map(|rank| (rank, entry.path())))
+            .min()
+            .map(|(_, path)| path)
+    }
+}
+
+/// Whether `path` has any name a library could treat as a manifest; for callers that
+/// only need to know a rescan may be due.
+pub fn is_manifest_candidate(path: &Path) -> bool {
+    ManifestNames::new(true).is_manifest(path)
+}
+
+/// Upgrades a manifest from `index` to `index + 1`.
+type Migration = fn(Mapping) -> Mapping;
+
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+/// A list field that may also be written as a single string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StringList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StringList {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            StringList::One(value) => vec![value],
+            StringList::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ScalarValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl ScalarValue {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            ScalarValue::Int(value) => Some(*value as f64),
+            ScalarValue::Float(value) => Some(*value),
+            ScalarValue::Str(value) => value.trim().parse::<f64>().ok(),
+        }
+    }
+
+    pub fn into_text(self) -> String {
+        match self {
+            ScalarValue::Int(value) => value.to_string(),
+            ScalarValue::Float(value) => value.to_string(),
+            ScalarValue::Str(value) => value.trim().to_string(),
+        }
+    }
+}
+
+/// Descriptive fields beyond the core title/fansub/episodes set, normalized for display.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestMetadata {
+    pub aliases: Vec<String>,
+    pub original_title: String,
+    pub year: Option<i64>,
+    /// Broadcast season as `YYYY-MM`, or the manifest's own text when it is not a date.
+    pub season: String,
+    pub studio: String,
+    pub tags: Vec<String>,
+    /// `BD`, `WEB`, `TV` or `DVD` when recognized.
+    pub source: String,
+    pub rating: Option<f64>,
+    /// Provider (`anilist`, `bangumi`, `mal`, ...) to id.
+    pub external_ids: BTreeMap<String, String>,
+}
+
+/// Metadata sent by the create/edit form. `None` leaves the manifest's value untouched,
+/// while an empty value (or `0` for `year` and `rating`) removes the key.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MetadataUpdate {
+    aliases: Option<Vec<String>>,
+    original_title: Option<String>,
+    year: Option<i64>,
+    season: Option<String>,
+    studio: Option<String>,
+    tags: Option<Vec<String>>,
+    source: Option<String>,
+    rating: Option<f64>,
+    external_ids: Option<BTreeMap<String, String>>,
+}
+
+fn text_value(value: &str) -> Value {
+    let value = value.trim();
+    if value.is_empty() {
+        Value::Null
+    } else {
+        Value::from(value)
+    }
+}
+
+fn list_value(values: Vec<String>) -> Value {
+    if values.is_empty() {
+        Value::Null
+    } else {
+        Value::Sequence(values.into_iter().map(Value::from).collect())
+    }
+}
+
+impl MetadataUpdate {
+    /// Writes the provided fields into a manifest document.
+    pub fn apply(self, document: &mut Mapping) {
+        let fields = [
+            ("aliases", self.aliases.map(|values| list_value(normalize_list(values)))),
+            ("original_title", self.original_title.map(|value| text_value(&value))),
+            (
+                "year",
+                self.year
+                    .map(|value| normalize_year(value).map(Value::from).unwrap_or(Value::Null)),
+            ),
+            ("season", self.season.map(|value| text_value(&normalize_season(&value)))),
+            ("studio", self.studio.map(|value| text_value(&value))),
+            ("tags", self.tags.map(|values| list_value(normalize_tags(values)))),
+            ("source", self.source.map(|value| text_value(&normalize_source(&value)))),
+            (
+                "rating",
+                self.rating
+                    .map(|value| normalize_rating(value).map(Value::from).unwrap_or(Value::Null)),
+            ),
+            (
+                "external_ids",
+                self.external_ids.map(|ids| {
+                    let ids = normalize_external_ids(
+                        ids.into_iter().map(|(provider, id)| (provider, Some(ScalarValue::Str(id)))),
+                    );
+                    if ids.is_empty() {
+                        return Value::Null;
+                    }
+                    let mut mapping = Mapping::new();
+                    for (provider, id) in ids {
+                        // Numeric ids are written unquoted, the way people type them.
+                        let value = id.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::from(id));
+                        mapping.insert(Value::from(provider), value);
+                    }
+                    Value::Mapping(mapping)
+                }),
+            ),
+        ];
+        for (key, value) in fields {
+            match value {
+                None => {}
+                Some(Value::Null) => {
+                    document.remove(key);
+                }
+                Some(value) => {
+                    document.insert(Value::from(key), value);
+                }
+            }
+        }
+    }
+}
+
+/// Trims entries, drops empty ones and keeps the first of any duplicates.
+fn normalize_list(values: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    values
+        .into_iter()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty() && seen.insert(value.clone()))
+        .collect()
+}
+
+/// Like `normalize_list`, but tags that differ only in case are duplicates.
+fn normalize_tags(values: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    normalize_list(values)
+        .into_iter()
+        .filter(|value| seen.insert(value.to_lowercase()))
+        .collect()
+}
+
+fn normalize_year(value: i64) -> Option<i64> {
+    (1900..=2100).contains(&value).then_some(value)
+}
+
+/// Normalizes `2024-10`, `2024/10`, `2024.10`, `2024年10月` and `2024 秋`/`Fall 2024` to `YYYY-MM`.
+fn normalize_season(value: &str) -> String {
+    let value = value.trim();
+    let lower = value.to_lowercase();
+    let numbers = lower
+        .split(|ch: char| !ch.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>();
+    let year = numbers
+        .iter()
+        .find(|part| part.len() == 4)
+        .and_then(|part| part.parse::<i64>().ok())
+        .and_then(normalize_year);
+    let Some(year) = year else {
+        return value.to_string();
+    };
+
+    let quarter = [
+        (&["冬", "winter"][..], 1),
+        (&["春", "spring"][..], 4),
+        (&["夏", "summer"][..], 7),
+        (&["秋", "fall", "autumn"][..], 10),
+    ]
+    .iter()
+    .find(|(names, _)| names.iter().any(|name| lower.contains(name)))
+    .map(|(_, month)| *month);
+    let month = numbers
+        .iter()
+        .filter(|part| part.len() <= 2)
+        .find_map(|part| part.parse::<u32>().ok().filter(|month| (1..=12).contains(month)))
+        .or(quarter);
+
+    match month {
+        Some(month) => format!("{}-{:02}", year, month),
+        None => value.to_string(),
+    }
+}
+
+fn normalize_source(value: &str) -> String {
+    let value = value.trim();
+    let key = value.to_lowercase().replace(['-', ' ', '_'], "");
+    let canonical = match key.as_str() {
+        "bd" | "bdrip" | "bdremux" | "bluray" | "bdmv" => "BD",
+        "web" | "webdl" | "webrip" => "WEB",
+        "tv" | "tvrip" | "hdtv" => "TV",
+        "dvd" | "dvdrip" => "DVD",
+        _ => return value.to_string(),
+    };
+    canonical.to_string()
+}
+
+fn normalize_rating(value: f64) -> Option<f64> {
+    (value.is_finite() && value > 0.0 && value <= 10.0).then_some(value)
+}
+
+/// Drops providers left without an id, such as `mal:` with nothing after it.
+fn normalize_external_ids(ids: impl IntoIterator<Item = (String, Option<ScalarValue>)>) -> BTreeMap<String, String> {
+    ids.into_iter()
+        .filter_map(|(provider, id)| Some((provider.trim().to_lowercase(), id?.into_text())))
+        .filter(|(provider, id)| !provider.is_empty() && !id.is_empty())
+        .collect()
+}
+
+/// Fields as they appear in `manifest.yml`, before normalization.
+#[derive(Debug, Default)]
+pub struct RawMetadata {
+    pub aliases: Option<StringList>,
+    pub original_title: Option<String>,
+    pub year: Option<ScalarValue>,
+    pub season: Option<ScalarValue>,
+    pub studio: Option<String>,
+    pub tags: Option<StringList>,
+    pub source: Option<String>,
+    pub rating: Option<ScalarValue>,
+    pub external_ids: Option<BTreeMap<String, Option<ScalarValue>>>,
+}
+
+impl ManifestMetadata {
+    pub fn from_raw(raw: RawMetadata) -> Self {
+        let season = raw
+            .season
+            .map(|value| normalize_season(&value.into_text()))
+            .unwrap_or_default();
+        let year = raw
+            .year
+            .and_then(|value| value.as_f64())
+            .and_then(|value| normalize_year(value as i64))
+            // `season: 2024-10` already says which year it aired.
+            .or_else(|| season.get(..4).and_then(|prefix| prefix.parse::<i64>().ok()).and_then(normalize_year));
+
+        Self {
+            aliases: normalize_list(raw.aliases.map(StringList::into_vec).unwrap_or_default()),
+            original_title: raw.original_title.unwrap_or_default().trim().to_string(),
+            year,
+            season,
+            studio: raw.studio.unwrap_or_default().trim().to_string(),
+            tags: normalize_tags(raw.tags.map(StringList::into_vec).unwrap_or_default()),
+            source: raw.source.map(|value| normalize_source(&value)).unwrap_or_default(),
+            rating: raw.rating.and_then(|value| value.as_f64()).and_then(normalize_rating),
+            external_ids: normalize_external_ids(raw.external_ids.unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ManifestIssueKind {
+    /// A spelling of a known key that older manifests used, e.g. `subtitle-type`.
+    Deprecated,
+    /// An unknown key close to a known one, e.g. `subtile_type`.
+    Misspelled,
+    Unknown,
+    /// Written by a newer version of the app.
+    UnsupportedVersion,
+    Invalid,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestIssue {
+    kind: ManifestIssueKind,
+    key: Option<String>,
+    suggestion: Option<String>,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestValidation {
+    path: String,
+    schema_version: u64,
+    /// Older than `CURRENT_SCHEMA_VERSION`; `migrate_manifests` would rewrite it.
+    outdated: bool,
+    issues: Vec<ManifestIssue>,
+}
+
+/// Folds `subtitleType`, `subtitle-type` and `Title` into `subtitle_type` and `title`.
+fn canonical_key(key: &str) -> String {
+    let mut canonical = String::with_capacity(key.len() + 4);
+    let mut previous_lower = false;
+    for ch in key.trim().chars() {
+        if ch == '-' || ch == ' ' {
+            canonical.push('_');
+            previous_lower = false;
+        } else if ch.is_uppercase() {
+            if previous_lower {
+                canonical.push('_');
+            }
+            canonical.extend(ch.to_lowercase());
+            previous_lower = false;
+        } else {
+            canonical.push(ch);
+            previous_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        }
+    }
+    canonical
+}
+
+pub fn known_key(key: &str) -> Option<&'static str> {
+    let canonical = canonical_key(key);
+    KNOWN_KEYS.iter().copied().find(|known| *known == canonical)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn closest_known_key(key: &str) -> Option<&'static str> {
+    let canonical = canonical_key(key);
+    KNOWN_KEYS
+        .iter()
+        .copied()
+        .map(|known| (edit_distance(&canonical, known), known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+fn schema_version(mapping: &Mapping) -> u64 {
+    mapping
+        .get(SCHEMA_VERSION_KEY)
+        .and_then(|value| value.as_u64().or_else(|| value.as_str().and_then(|text| text.trim().parse().ok())))
+        .unwrap_or(0)
+}
+
+/// Version 1 introduced `schema_version` and settled on snake_case keys.
+fn migrate_v0_to_v1(mapping: Mapping) -> Mapping {
+    let mut migrated = Mapping::new();
+    for (key, value) in &mapping {
+        let Some(name) = key.as_str() else {
+            migrated.insert(key.clone(), value.clone());
+            continue;
+        };
+        match known_key(name) {
+            // An exact key always wins over an alias of it, wherever the alias appears.
+            Some(known) if known != name && mapping.contains_key(known) => {}
+            Some(known) if !migrated.contains_key(known) => {
+                migrated.insert(Value::from(known), value.clone());
+            }
+            Some(_) => {}
+            None => {
+                migrated.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    migrated
+}
+
+/// Runs every migration after `version` and stamps the current version.
+fn upgrade(mut mapping: Mapping, version: u64) -> Mapping {
+    for migration in &MIGRATIONS[version as usize..] {
+        mapping = migration(mapping);
+    }
+    mapping.remove(SCHEMA_VERSION_KEY);
+
+    let mut stamped = Mapping::new();
+    stamped.insert(Value::from(SCHEMA_VERSION_KEY), Value::from(CURRENT_SCHEMA_VERSION));
+    stamped.extend(mapping);
+    stamped
+}
+
+/// Upgrades an older manifest to the current schema.
+///
+/// Returns `None` when the manifest is already current (or newer, which is read as-is).
+fn migrate(mapping: Mapping) -> Option<Mapping> {
+    let version = schema_version(&mapping);
+    (version < CURRENT_SCHEMA_VERSION).then(|| upgrade(mapping, version))
+}
+
+/// Turns the TOML deserializer's wrapped dates, such as `season = 2024-10-01`, back into text.
+fn unwrap_toml_datetimes(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => {
+            if mapping.len() == 1 {
+                if let Some(Value::String(datetime)) = mapping.get(TOML_DATETIME_KEY) {
+                    return Value::from(datetime.as_str());
+                }
+            }
+            Value::Mapping(
+                mapping
+                    .into_iter()
+                    .map(|(key, value)| (key, unwrap_toml_datetimes(value)))
+                    .collect(),
+            )
+        }
+        Value::Sequence(values) => Value::Sequence(values.into_iter().map(unwrap_toml_datetimes).collect()),
+        value => value,
+    }
+}
+
+/// Reads a manifest of any format into a YAML value, keeping the order keys were written in.
+fn parse_value(content: &str, format: ManifestFormat) -> Result<Value, ManifestError> {
+    match format {
+        ManifestFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+        ManifestFormat::Json => serde_json::from_str(content).map_err(ManifestError::json),
+        ManifestFormat::Toml => toml::from_str(content)
+            .map(unwrap_toml_datetimes)
+            .map_err(|err| ManifestError::toml(err, content)),
+    }
+}
+
+/// TOML has no null; leaving the key out reads the same.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Sequence(values) => Value::Sequence(
+            values
+                .into_iter()
+                .filter(|value| !value.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Writes a manifest document in `format`.
+pub fn render(document: &Mapping, format: ManifestFormat) -> Result<String, String> {
+    let content = match format {
+        ManifestFormat::Yaml => serde_yaml::to_string(document).map_err(|err| err.to_string()),
+        ManifestFormat::Json => serde_json::to_string_pretty(document)
+            .map(|content| content + "\n")
+            .map_err(|err| err.to_string()),
+        ManifestFormat::Toml => {
+            let document = without_nulls(Value::Mapping(document.clone()));
+            toml::to_string_pretty(&document).map_err(|err| err.to_string())
+        }
+    };
+    content.map_err(|err| format!("Failed to build manifest content: {}", err))
+}
+
+/// Parses a manifest into a document in the current schema, for editing.
+pub fn load_document(content: &str, format: ManifestFormat) -> Option<Mapping> {
+    match parse_value(content, format).ok()? {
+        Value::Mapping(mapping) => Some(migrate(mapping.clone()).unwrap_or(mapping)),
+        _ => None,
+    }
+}
+
+/// Parses a manifest, upgrading older schema versions in memory.
+pub fn parse_manifest(content: &str, format: ManifestFormat) -> Result<ManifestRaw, ManifestError> {
+    let value = parse_value(content, format)?;
+    let version = value.as_mapping().map(schema_version).unwrap_or(CURRENT_SCHEMA_VERSION);
+    match value {
+        Value::Mapping(mapping) if version < CURRENT_SCHEMA_VERSION => {
+            Ok(serde_yaml::from_value(Value::Mapping(upgrade(mapping, version)))?)
+        }
+        // Parsing YAML text again keeps line/column information in type errors.
+        _ if format == ManifestFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+        value => Ok(serde_yaml::from_value(value)?),
+    }
+}
+
+fn validate_mapping(mapping: &Mapping) -> (u64, Vec<ManifestIssue>) {
+    let version = schema_version(mapping);
+    let mut issues = Vec::new();
+    if version > CURRENT_SCHEMA_VERSION {
+        issues.push(ManifestIssue {
+            kind: ManifestIssueKind::UnsupportedVersion,
+            key: Some(SCHEMA_VERSION_KEY.to_string()),
+            suggestion: None,
+            message: format!(
+                "Schema version {} is newer than the supported version {}",
+                version, CURRENT_SCHEMA_VERSION
+            ),
+        });
+    }
+
+    for key in mapping.keys() {
+        let Some(name) = key.as_str() else {
+            issues.push(ManifestIssue {
+                kind: ManifestIssueKind::Unknown,
+                key: None,
+                suggestion: None,
+                message: "Manifest keys must be strings".to_string(),
+            });
+            continue;
+        };
+        if KNOWN_KEYS.contains(&name) {
+            continue;
+        }
+        let alias = known_key(name);
+        let issue = if let Some(known) = alias.filter(|_| version >= 1) {
+            // Aliases are only folded in when upgrading from version 0; later versions read
+            // exact keys, so the value is dropped.
+            ManifestIssue {
+                kind: ManifestIssueKind::Unknown,
+                key: Some(name.to_string()),
+                suggestion: Some(known.to_string()),
+                message: format!(
+                    "Unknown key `{}` is ignored in schema version {}, rename it to `{}`",
+                    name, version, known
+                ),
+            }
+        } else if let Some(known) = alias {
+            let message = if mapping.contains_key(known) {
+                format!("`{}` is ignored because `{}` is also set", name, known)
+            } else {
+                format!("`{}` is deprecated, use `{}`", name, known)
+            };
+            ManifestIssue {
+                kind: ManifestIssueKind::Deprecated,
+                key: Some(name.to_string()),
+                suggestion: Some(known.to_string()),
+                message,
+            }
+        } else if let Some(known) = closest_known_key(name) {
+            ManifestIssue {
+                kind: ManifestIssueKind::Misspelled,
+                key: Some(name.to_string()),
+                suggestion: Some(known.to_string()),
+                message: format!("Unknown key `{}`, did you mean `{}`?", name, known),
+            }
+        } else {
+            ManifestIssue {
+                kind: ManifestIssueKind::Unknown,
+                key: Some(name.to_string()),
+                suggestion: None,
+                message: format!("Unknown key `{}` is ignored", name),
+            }
+        };
+        issues.push(issue);
+    }
+    (version, issues)
+}
+
+fn validate_file(path: &Path) -> ManifestValidation {
+    let invalid = |message: String| ManifestValidation {
+        path: normalize_path(path),
+        schema_version: 0,
+        outdated: false,
+        issues: vec![ManifestIssue {
+            kind: ManifestIssueKind::Invalid,
+            key: None,
+            suggestion: None,
+            message,
+        }],
+    };
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => return invalid(format!("Failed to read manifest: {}", err)),
+    };
+    let format = ManifestFormat::from_path(path);
+    let mapping = match parse_value(&content, format) {
+        Ok(Value::Mapping(mapping)) => mapping,
+        Ok(Value::Null) => Mapping::new(),
+        Ok(_) => return invalid("Manifest must be a mapping of keys to values".to_string()),
+        Err(err) => return invalid(err.to_string()),
+    };
+
+    let (schema_version, mut issues) = validate_mapping(&mapping);
+    if let Err(err) = parse_manifest(&content, format) {
+        issues.push(ManifestIssue {
+            kind: ManifestIssueKind::Invalid,
+            key: None,
+            suggestion: None,
+            message: err.to_string(),
+        });
+    }
+    ManifestValidation {
+        path: normalize_path(path),
+        schema_version,
+        outdated: schema_version < CURRENT_SCHEMA_VERSION,
+        issues,
+    }
+}
+
+fn manifest_paths(app: &AppHandle, base: &Path) -> Vec<PathBuf> {
+    let settings = libraries::scan_settings(app, base);
+    settings
+        .walk_dir(base)
+        .into_iter()
+        .filter_entry(|entry| settings.ignore.check(entry.path(), entry.file_type().is_dir()).is_none())
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && settings.manifest_names.is_manifest(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Lists manifests that are outdated or use deprecated, misspelled or unknown keys.
+#[tauri::command]
+pub fn validate_manifests(app: AppHandle, base_dir: String) -> Result<Vec<ManifestValidation>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+    Ok(manifest_paths(&app, &base)
+        .iter()
+        .map(|path| validate_file(path))
+        .filter(|result| result.outdated || !result.issues.is_empty())
+        .collect())
+}
+
+/// Rewrites outdated manifests in the current schema and returns the paths it changed.
+///
+/// Deprecated keys are renamed in place; comments and formatting are kept where the
+/// layout allows it.
+#[tauri::command]
+pub fn migrate_manifests(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+) -> Result<Vec<String>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+
+    let mut migrated = Vec::new();
+    for path in manifest_paths(&app, &base) {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let format = ManifestFormat::from_path(&path);
+        let Ok(Value::Mapping(mapping)) = parse_value(&content, format) else {
+            continue;
+        };
+        let Some(upgraded) = migrate(mapping) else {
+            continue;
+        };
+        let patched = match format {
+            ManifestFormat::Yaml => manifest_patch::patch_manifest(&content, &upgraded, &upgraded),
+            _ => None,
+        };
+        let content = match patched {
+            Some(content) => content,
+            None => render(&upgraded, format)?,
+        };
+        history::save_manifest(&database, &base, &path, &content)?;
+        migrated.push(normalize_path(&path));
+    }
+    Ok(migrated)
+}
+
+/// Rewrites a manifest in another format next to the original, which is then removed.
+/// Keys keep their order, except that TOML puts nested tables such as `external_ids`
+/// last; comments, which JSON can't hold, are not carried over.
+///
+/// Returns the path of the new manifest, or the original when it already is in `format`.
+#[tauri::command]
+pub fn convert_manifest(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    manifest_path: String,
+    format: ManifestFormat,
+) -> Result<String, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+    let source = PathBuf::from(manifest_path.trim());
+    let names = libraries::scan_settings(&app, &base).manifest_names;
+    convert_file(&database, &base, &source, &names, format)
+}
+
+fn convert_file(
+    database: &Database,
+    base: &Path,
+    source: &Path,
+    names: &ManifestNames,
+    format: ManifestFormat,
+) -> Result<String, String> {
+    let canonical_base =
+        fs::canonicalize(base).map_err(|err| format!("Failed to resolve {}: {}", base.display(), err))?;
+    let canonical_source =
+        fs::canonicalize(source).map_err(|err| format!("Failed to resolve {}: {}", source.display(), err))?;
+    if !canonical_source.starts_with(&canonical_base) {
+        return Err(format!("{} 不在资料库目录中", source.display()));
+    }
+    if !names.is_manifest(source) {
+        return Err(format!("{} 不是清单文件", source.display()));
+    }
+    let source_format = ManifestFormat::from_path(source);
+    if source_format == format {
+        return Ok(normalize_path(source));
+    }
+
+    let content = fs::read_to_string(source).map_err(|err| format!("Failed to read manifest: {}", err))?;
+    let parsed =
+        parse_value(&content, source_format).map_err(|err| format!("Failed to parse manifest: {}", err))?;
+    let document = match parsed {
+        Value::Mapping(mapping) => mapping,
+        Value::Null => Mapping::new(),
+        _ => return Err("Manifest must be a mapping of keys to values".to_string()),
+    };
+    let target = source.with_extension(format.extension());
+    if target.exists() {
+        return Err(format!("{} 已存在", target.display()));
+    }
+
+    history::save_manifest(database, base, &target, &render(&document, format)?)?;
+    history::remove_manifest(database, base, source)?;
+    Ok(normalize_path(&target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue_kinds(content: &str) -> Vec<ManifestIssueKind> {
+        let mapping = serde_yaml::from_str::<Mapping>(content).unwrap();
+        validate_mapping(&mapping).1.into_iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn aliases_are_only_deprecated_before_version_1() {
+        assert_eq!(issue_kinds("Title: Frieren\nsubtitleType: 简中\n"), [
+            ManifestIssueKind::Deprecated,
+            ManifestIssueKind::Deprecated
+        ]);
+        assert_eq!(issue_kinds("schema_version: 1\nTitle: Frieren\nsubtitleType: 简中\n"), [
+            ManifestIssueKind::Unknown,
+            ManifestIssueKind::Unknown
+        ]);
+    }
+
+    #[test]
+    fn manifest_names_rank_exact_case_first() {
+        let names = ManifestNames::default();
+        let rank = |name: &str| names.rank(Path::new("/lib/A").join(name).as_path());
+        assert_eq!(rank("manifest.yml"), Some(0));
+        assert_eq!(rank("Manifest.YML"), Some(1));
+        assert!(rank("manifest.yaml") > rank("MANIFEST.YML"));
+        assert!(rank("manifest.toml") > rank("manifest.json"));
+        assert_eq!(rank("manifest.yml.bak"), None);
+        assert_eq!(rank(".anime.yml"), None);
+
+        let hidden = ManifestNames::new(true);
+        assert!(hidden.rank(Path::new(".ANIME.yml")) > hidden.rank(Path::new("manifest.toml")));
+    }
+
+    #[test]
+    fn find_discovers_any_case_and_keeps_the_best_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = ManifestNames::default();
+        assert_eq!(names.find(dir.path()), None);
+
+        fs::write(dir.path().join("MANIFEST.TOML"), "").unwrap();
+        assert_eq!(names.find(dir.path()), Some(dir.path().join("MANIFEST.TOML")));
+
+        fs::write(dir.path().join("Manifest.Yaml"), "").unwrap();
+        fs::write(dir.path().join("manifest.json"), "").unwrap();
+        assert_eq!(names.find(dir.path()), Some(dir.path().join("Manifest.Yaml")));
+
+        // A folder named like a manifest is not one.
+        fs::create_dir(dir.path().join("manifest.yml")).unwrap();
+        assert_eq!(names.find(dir.path()), Some(dir.path().join("Manifest.Yaml")));
+
+        fs::write(dir.path().join(".anime.yml"), "").unwrap();
+        assert_eq!(names.find(dir.path()), Some(dir.path().join("Manifest.Yaml")));
+        assert_eq!(ManifestNames::new(true).find(dir.path()), Some(dir.path().join("Manifest.Yaml")));
+    }
+
+    fn round_trip(document: &Mapping, formats: &[ManifestFormat]) -> Mapping {
+        let mut current = document.clone();
+        for &format in formats {
+            let content = render(&current, format).unwrap();
+            current = match parse_value(&content, format).unwrap() {
+                Value::Mapping(mapping) => mapping,
+                other => panic!("{:?} is not a mapping", other),
+            };
+        }
+        current
+    }
+
+    #[test]
+    fn documents_survive_yaml_json_toml_and_back() {
+        let content = "schema_version: 1\ntitle: 葬送的芙莉莲\nepisodes: 28\nrating: 9.5\nis_parent: false\n\
+                       tags:\n- 奇幻\n- 冒险\nexternal_ids:\n  anilist: '154587'\n  bangumi: '400602'\nnote: \"多行\\n备注\"\n";
+        let document = serde_yaml::from_str::<Mapping>(content).unwrap();
+
+        let back = round_trip(&document, &[ManifestFormat::Json, ManifestFormat::Toml, ManifestFormat::Yaml]);
+        assert_eq!(back, document);
+        // TOML writes tables after plain keys; everything else keeps its place.
+        let order = |mapping: &Mapping| {
+            mapping
+                .keys()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let mut expected = order(&document);
+        expected.retain(|key| key != "external_ids");
+        expected.push("external_ids".to_string());
+        assert_eq!(order(&back), expected);
+        let raw = parse_manifest(&render(&back, ManifestFormat::Yaml).unwrap(), ManifestFormat::Yaml).unwrap();
+        assert_eq!(raw.title.as_deref(), Some("葬送的芙莉莲"));
+    }
+
+    #[test]
+    fn toml_leaves_out_nulls_and_reads_dates_as_text() {
+        let document = serde_yaml::from_str::<Mapping>(
+            "title: Frieren\nstudio: null\ntags: [奇幻, null]\nexternal_ids:\n  anilist: null\n  mal: '52991'\n",
+        )
+        .unwrap();
+        let content = render(&document, ManifestFormat::Toml).unwrap();
+        assert!(!content.contains("studio"));
+        let expected = serde_yaml::from_str::<Mapping>(
+            "title: Frieren\ntags: [奇幻]\nexternal_ids:\n  mal: '52991'\n",
+        )
+        .unwrap();
+        assert_eq!(round_trip(&document, &[ManifestFormat::Toml]), expected);
+
+        let dated = parse_value("title = \"Frieren\"\nseason = 2023-09-29\n", ManifestFormat::Toml).unwrap();
+        assert_eq!(dated["season"], Value::from("2023-09-29"));
+    }
+
+    #[test]
+    fn convert_replaces_the_source_and_refuses_to_overwrite() {
+        let library = tempfile::tempdir().unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let database = Database::new(data.path().join("index.sqlite"));
+        let names = ManifestNames::default();
+        let base = library.path();
+        let folder = base.join("Frieren");
+        fs::create_dir(&folder).unwrap();
+        let source = folder.join("manifest.yml");
+        fs::write(&source, "title: Frieren\nepisodes: 28\n").unwrap();
+
+        assert_eq!(
+            convert_file(&database, base, &source, &names, ManifestFormat::Yaml).unwrap(),
+            normalize_path(&source)
+        );
+
+        let existing = folder.join("manifest.json");
+        fs::write(&existing, "{\"title\": \"Other\"}\n").unwrap();
+        assert!(convert_file(&database, base, &source, &names, ManifestFormat::Json).is_err());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "{\"title\": \"Other\"}\n");
+        assert!(source.exists());
+
+        let target = convert_file(&database, base, &source, &names, ManifestFormat::Toml).unwrap();
+        assert_eq!(target, normalize_path(&folder.join("manifest.toml")));
+        assert!(!source.exists());
+        let converted = fs::read_to_string(folder.join("manifest.toml")).unwrap();
+        let raw = parse_manifest(&converted, ManifestFormat::Toml).unwrap();
+        assert_eq!(raw.title.as_deref(), Some("Frieren"));
+    }
+}
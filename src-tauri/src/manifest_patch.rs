@@ -0,0 +1,322 @@
+use regex::Regex;
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use crate::manifest::{known_key, SCHEMA_VERSION_KEY};
+
+/// A plain top-level key at column 0, e.g. `title:` or `subtitle-type: 简中`.
+static TOP_LEVEL_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^([^\s#'"\-?:{}\[\],&*!|>%@`][^:#]*?)\s*:(?:\s|$)"#).unwrap());
+
+enum Block {
+    /// Blank lines, comments and document markers between entries.
+    Other(String),
+    Entry { key: String, lines: Vec<String> },
+}
+
+fn split_blocks(content: &str) -> Option<Vec<Block>> {
+    let mut blocks = Vec::<Block>::new();
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let continues_value = line.starts_with([' ', '\t']) || (line.starts_with('-') && !line.starts_with("---"));
+        if continues_value {
+            // Blank lines inside a block scalar or list belong to the entry above them.
+            let entry = blocks
+                .iter()
+                .rposition(|block| matches!(block, Block::Entry { .. }))?;
+            let pending = blocks.split_off(entry + 1);
+            let Some(Block::Entry { lines, .. }) = blocks.last_mut() else {
+                return None;
+            };
+            for block in pending {
+                if let Block::Other(other) = block {
+                    lines.push(other);
+                }
+            }
+            lines.push(line.to_string());
+        } else if trimmed.is_empty() || trimmed.starts_with('#') || line == "---" || line == "..." {
+            blocks.push(Block::Other(line.to_string()));
+        } else {
+            let key = TOP_LEVEL_KEY.captures(line)?[1].to_string();
+            blocks.push(Block::Entry {
+                key,
+                lines: vec![line.to_string()],
+            });
+        }
+    }
+
+    // Trailing blank lines and comments stay outside the last entry.
+    if let Some(entry) = blocks.iter().rposition(|block| matches!(block, Block::Entry { .. })) {
+        let mut trailing = Vec::new();
+        if let Block::Entry { lines, .. } = &mut blocks[entry] {
+            while lines.len() > 1 && lines.last().map(|line| is_filler(line)).unwrap_or(false) {
+                trailing.push(Block::Other(lines.pop().unwrap_or_default()));
+            }
+        }
+        trailing.reverse();
+        blocks.splice(entry + 1..entry + 1, trailing);
+    }
+    Some(blocks)
+}
+
+fn is_filler(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+/// Returns the ` # comment` at the end of a single-line entry, if any.
+fn inline_comment(line: &str) -> Option<&str> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, ch) in line.char_indices() {
+        match (quote, ch) {
+            (None, '\'' | '"') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '#') if previous.is_whitespace() => return Some(line[index..].trim_end()),
+            _ => {}
+        }
+        previous = ch;
+    }
+    None
+}
+
+fn render(key: &str, value: &Value) -> Option<Vec<String>> {
+    let mut single = Mapping::new();
+    single.insert(Value::from(key), value.clone());
+    let rendered = serde_yaml::to_string(&single).ok()?;
+    Some(rendered.lines().map(|line| line.to_string()).collect())
+}
+
+fn rename_key(lines: &[String], from: &str, to: &str) -> Vec<String> {
+    let mut renamed = lines.to_vec();
+    if let Some(first) = renamed.first_mut() {
+        *first = format!("{}{}", to, &first[from.len()..]);
+    }
+    renamed
+}
+
+/// Rewrites only the top-level keys whose values differ between `original` and `updated`,
+/// keeping comments, blank lines, key order and untouched values byte for byte.
+///
+/// `original` is what `content` parses to after schema migration, so keys the migration
+/// renamed (`subtitle-type` → `subtitle_type`) are renamed in place. Returns `None` for
+/// layouts this line-based editor does not handle (flow mappings, quoted or complex keys),
+/// or when the patched text would not parse back to `updated`.
+pub fn patch_manifest(content: &str, original: &Mapping, updated: &Mapping) -> Option<String> {
+    let (bom, body) = match content.strip_prefix('\u{feff}') {
+        Some(body) => ("\u{feff}", body),
+        None => ("", content),
+    };
+    let newline = if body.contains("\r\n") { "\r\n" } else { "\n" };
+    let blocks = split_blocks(body)?;
+
+    // Aliases the migration folded into a known key; the exact key wins, then the first alias.
+    let mut alias_owner = HashMap::<&'static str, usize>::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if let Block::Entry { key, .. } = block {
+            if original.contains_key(key.as_str()) {
+                continue;
+            }
+            let canonical = known_key(key)?;
+            let exact = blocks
+                .iter()
+                .any(|other| matches!(other, Block::Entry { key, .. } if key == canonical));
+            if !exact {
+                alias_owner.entry(canonical).or_insert(index);
+            }
+        }
+    }
+
+    let mut written = HashSet::<String>::new();
+    let mut output = Vec::<String>::new();
+    let mut first_entry = None;
+    for (index, block) in blocks.iter().enumerate() {
+        let (key, lines) = match block {
+            Block::Other(line) => {
+                output.push(line.clone());
+                continue;
+            }
+            Block::Entry { key, lines } => (key.as_str(), lines),
+        };
+        first_entry.get_or_insert(output.len());
+
+        let target = if original.contains_key(key) {
+            key
+        } else {
+            match known_key(key) {
+                Some(canonical) if alias_owner.get(canonical) == Some(&index) => canonical,
+                // An alias shadowed by its exact key was dropped on load.
+                _ => continue,
+            }
+        };
+        let Some(value) = updated.get(target) else {
+            continue;
+        };
+        written.insert(target.to_string());
+
+        if original.get(target) == Some(value) {
+            if target == key {
+                output.extend(lines.iter().cloned());
+            } else {
+                output.extend(rename_key(lines, key, target));
+            }
+            continue;
+        }
+
+        let mut replacement = render(target, value)?;
+        if let ([old], [new]) = (lines.as_slice(), replacement.as_mut_slice()) {
+            if let Some(comment) = inline_comment(old) {
+                *new = format!("{} {}", new, comment);
+            }
+        }
+        output.extend(replacement);
+    }
+
+    let mut leading = Vec::new();
+    let mut trailing = Vec::new();
+    for (key, value) in updated {
+        let name = key.as_str()?;
+        if written.contains(name) {
+            continue;
+        }
+        let rendered = render(name, value)?;
+        if name == SCHEMA_VERSION_KEY {
+            leading.extend(rendered);
+        } else {
+            trailing.extend(rendered);
+        }
+    }
+    let insert_at = first_entry.unwrap_or(output.len());
+    output.splice(insert_at..insert_at, leading);
+    // New keys go after the last entry, before any trailing comments.
+    let append_at = output
+        .iter()
+        .rposition(|line| !is_filler(line))
+        .map(|index| index + 1)
+        .unwrap_or(output.len());
+    output.splice(append_at..append_at, trailing);
+
+    let patched = format!("{}{}", output.join(newline), newline);
+    // Checked without the BOM, which serde_yaml reads as the start of a second document.
+    match serde_yaml::from_str::<Value>(&patched).ok()? {
+        Value::Mapping(result) if &result == updated => Some(format!("{}{}", bom, patched)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Mapping {
+        serde_yaml::from_str(content.trim_start_matches('\u{feff}')).unwrap()
+    }
+
+    /// Patches `content` with `edit` applied to what it parses to.
+    fn patch(content: &str, edit: impl FnOnce(&mut Mapping)) -> Option<String> {
+        let original = parse(content);
+        let mut updated = original.clone();
+        edit(&mut updated);
+        patch_manifest(content, &original, &updated)
+    }
+
+    #[test]
+    fn comments_and_inline_comments_are_kept() {
+        let content = "# 番剧信息\ntitle: Frieren # 暂定\n\n# 集数\nepisodes: 28\nnote: 周五更新 # 提醒\n";
+        let patched = patch(content, |document| {
+            document.insert("title".into(), "Sousou no Frieren".into());
+        });
+        assert_eq!(
+            patched.as_deref(),
+            Some("# 番剧信息\ntitle: Sousou no Frieren # 暂定\n\n# 集数\nepisodes: 28\nnote: 周五更新 # 提醒\n")
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_key_order_are_kept() {
+        let content = "year: 2020\nmy_note: keep me\ntitle: A\n";
+        let patched = patch(content, |document| {
+            document.insert("year".into(), 2021.into());
+            document.insert("studio".into(), "MAPPA".into());
+        });
+        assert_eq!(patched.as_deref(), Some("year: 2021\nmy_note: keep me\ntitle: A\nstudio: MAPPA\n"));
+    }
+
+    #[test]
+    fn new_keys_go_before_trailing_comments_and_schema_version_first() {
+        let content = "title: A\n\n# end\n";
+        let patched = patch(content, |document| {
+            document.insert("year".into(), 2021.into());
+            document.insert(SCHEMA_VERSION_KEY.into(), 1.into());
+        });
+        assert_eq!(patched.as_deref(), Some("schema_version: 1\ntitle: A\nyear: 2021\n\n# end\n"));
+    }
+
+    #[test]
+    fn crlf_line_endings_and_bom_are_kept() {
+        let content = "\u{feff}title: A\r\n# 年份\r\nyear: 2020\r\n";
+        let patched = patch(content, |document| {
+            document.insert("year".into(), 2021.into());
+        });
+        assert_eq!(patched.as_deref(), Some("\u{feff}title: A\r\n# 年份\r\nyear: 2021\r\n"));
+    }
+
+    #[test]
+    fn alias_keys_are_renamed_in_place() {
+        let content = "title: A\nsubtitle-type: 简中 # 内封\nquality: 1080p\n";
+        let mut original = Mapping::new();
+        original.insert("title".into(), "A".into());
+        original.insert("subtitle_type".into(), "简中".into());
+        original.insert("quality".into(), "1080p".into());
+
+        let unchanged = patch_manifest(content, &original, &original);
+        assert_eq!(
+            unchanged.as_deref(),
+            Some("title: A\nsubtitle_type: 简中 # 内封\nquality: 1080p\n")
+        );
+
+        let mut updated = original.clone();
+        updated.insert("subtitle_type".into(), "繁中".into());
+        let changed = patch_manifest(content, &original, &updated);
+        assert_eq!(changed.as_deref(), Some("title: A\nsubtitle_type: 繁中 # 内封\nquality: 1080p\n"));
+    }
+
+    #[test]
+    fn list_and_block_scalar_values_are_kept_or_replaced_whole() {
+        let content = "title: A\ntags:\n  - 奇幻\n  - 冒险\nnote: |\n  第一行\n\n  第二行\nyear: 2020\n";
+
+        let patched = patch(content, |document| {
+            document.insert("year".into(), 2021.into());
+        });
+        assert_eq!(
+            patched.as_deref(),
+            Some("title: A\ntags:\n  - 奇幻\n  - 冒险\nnote: |\n  第一行\n\n  第二行\nyear: 2021\n")
+        );
+
+        let patched = patch(content, |document| {
+            document.insert("tags".into(), Value::Sequence(vec!["奇幻".into()]));
+        });
+        assert_eq!(
+            patched.as_deref(),
+            Some("title: A\ntags:\n- 奇幻\nnote: |\n  第一行\n\n  第二行\nyear: 2020\n")
+        );
+    }
+
+    #[test]
+    fn unsupported_layouts_fall_back() {
+        // Flow mappings and quoted keys are left to a full rewrite.
+        assert_eq!(patch("{title: A, year: 2020}\n", |_| {}), None);
+        assert_eq!(patch("\"title\": A\n", |_| {}), None);
+    }
+
+    #[test]
+    fn failed_round_trip_falls_back() {
+        // Rewriting the anchor leaves the alias below pointing at nothing.
+        let content = "title: &name A\nnote: *name\n";
+        let patched = patch(content, |document| {
+            document.insert("title".into(), "B".into());
+        });
+        assert_eq!(patched, None);
+    }
+}
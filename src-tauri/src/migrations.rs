@@ -0,0 +1,465 @@
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+
+use crate::{collation, identity, search};
+
+/// A schema change applied once, in order, inside its own transaction.
+///
+/// Databases from 0.2.x and from builds before versioning report `user_version` 0 with
+/// some of these changes already made, so every step tolerates work that is already done.
+struct Migration {
+    description: &'static str,
+    apply: fn(&Connection) -> Result<(), String>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "entries and play history",
+        apply: create_base_tables,
+    },
+    Migration {
+        description: "manifest cache and scan diagnostics",
+        apply: create_scan_tables,
+    },
+    Migration {
+        description: "episode files and watch progress",
+        apply: create_episode_tables,
+    },
+    Migration {
+        description: "added date and phonetic sort keys",
+        apply: add_sort_columns,
+    },
+    Migration {
+        description: "manifest metadata",
+        apply: add_metadata_columns,
+    },
+    Migration {
+        description: "full-text search index",
+        apply: create_search_index,
+    },
+    Migration {
+        description: "manifest history",
+        apply: create_history_table,
+    },
+    Migration {
+        description: "library roots",
+        apply: create_roots_table,
+    },
+    Migration {
+        description: "entry identity",
+        apply: add_identity_columns,
+    },
+];
+
+/// Schema version written by this build, stored in `PRAGMA user_version`.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+fn user_version(conn: &Connection) -> Result<i64, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+        .map_err(|err| format!("Failed to read schema version: {}", err))
+}
+
+fn check_supported(version: i64) -> Result<(), String> {
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "数据库由更新版本的应用创建（架构版本 {}，当前版本支持到 {}），请升级应用后再打开",
+            version, SCHEMA_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Brings the database up to `SCHEMA_VERSION`, one committed step at a time, so an
+/// interrupted upgrade resumes from the last finished step. Refuses databases written
+/// by a newer version instead of guessing at their layout.
+pub fn migrate(conn: &Connection) -> Result<(), String> {
+    let version = user_version(conn)?;
+    check_supported(version)?;
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    loop {
+        // Immediate transactions keep two connections from running the same step.
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)
+            .map_err(|err| format!("Failed to start transaction: {}", err))?;
+        let version = user_version(&tx)?;
+        check_supported(version)?;
+        let Some(migration) = MIGRATIONS.get(version as usize) else {
+            return Ok(());
+        };
+        (migration.apply)(&tx).map_err(|err| {
+            format!("Failed to upgrade database to version {} ({}): {}", version + 1, migration.description, err)
+        })?;
+        tx.pragma_update(None, "user_version", version + 1)
+            .map_err(|err| format!("Failed to update schema version: {}", err))?;
+        tx.commit()
+            .map_err(|err| format!("Failed to commit transaction: {}", err))?;
+    }
+}
+
+/// Adds a column that databases created before it existed are missing.
+///
+/// Returns whether the column had to be added.
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|err| format!("Failed to inspect {}: {}", table, err))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|err| format!("Failed to inspect {}: {}", table, err))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .map_err(|err| format!("Failed to add {}.{}: {}", table, column, err))?;
+    }
+    Ok(!exists)
+}
+
+/// Cached manifests were parsed before a new field existed; re-read them on the next refresh.
+fn reset_manifest_cache(conn: &Connection) -> Result<(), String> {
+    conn.execute("DELETE FROM manifest_files", [])
+        .map_err(|err| format!("Failed to reset manifest cache: {}", err))?;
+    Ok(())
+}
+
+/// The schema shipped in 0.2.x.
+fn create_base_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS manifest_entries (
+            id TEXT PRIMARY KEY,
+            library_root TEXT NOT NULL,
+            title TEXT,
+            fansub TEXT,
+            subtitle_type TEXT,
+            episodes INTEGER,
+            quality TEXT,
+            note TEXT,
+            path TEXT,
+            folder_name TEXT,
+            group_name TEXT,
+            relative_dir TEXT,
+            updated_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_manifest_library ON manifest_entries (library_root);
+        CREATE TABLE IF NOT EXISTS play_history (
+            entry_id TEXT PRIMARY KEY,
+            last_played_path TEXT,
+            last_played_name TEXT,
+            updated_at INTEGER
+        );
+        ",
+    )
+    .map_err(|err| format!("Failed to create tables: {}", err))
+}
+
+fn create_scan_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS manifest_files (
+            path TEXT PRIMARY KEY,
+            library_root TEXT NOT NULL,
+            entry_id TEXT,
+            is_parent INTEGER NOT NULL DEFAULT 0,
+            parent_title TEXT,
+            modified_at INTEGER,
+            size INTEGER,
+            content_hash TEXT,
+            scanned_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_manifest_files_library ON manifest_files (library_root);
+        CREATE INDEX IF NOT EXISTS idx_manifest_files_entry ON manifest_files (entry_id);
+        CREATE TABLE IF NOT EXISTS scan_diagnostics (
+            library_root TEXT NOT NULL,
+            path TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            message TEXT,
+            line INTEGER,
+            column INTEGER,
+            detected_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_scan_diagnostics_library ON scan_diagnostics (library_root);
+        ",
+    )
+    .map_err(|err| format!("Failed to create tables: {}", err))
+}
+
+fn create_episode_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS episode_files (
+            path TEXT PRIMARY KEY,
+            entry_id TEXT NOT NULL,
+            library_root TEXT NOT NULL,
+            file_name TEXT,
+            relative_path TEXT,
+            episode_number REAL,
+            season INTEGER,
+            kind TEXT NOT NULL DEFAULT 'episode',
+            size INTEGER,
+            modified_at INTEGER,
+            updated_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_episode_files_entry ON episode_files (entry_id);
+        CREATE INDEX IF NOT EXISTS idx_episode_files_library ON episode_files (library_root);
+        CREATE TABLE IF NOT EXISTS watch_progress (
+            entry_id TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            watched INTEGER NOT NULL DEFAULT 0,
+            watch_count INTEGER NOT NULL DEFAULT 0,
+            first_watched_at INTEGER,
+            last_watched_at INTEGER,
+            position_seconds REAL,
+            updated_at INTEGER,
+            PRIMARY KEY (entry_id, file_path)
+        );
+        ",
+    )
+    .map_err(|err| format!("Failed to create tables: {}", err))
+}
+
+fn add_sort_columns(conn: &Connection) -> Result<(), String> {
+    ensure_column(conn, "manifest_entries", "added_at", "INTEGER")?;
+    ensure_column(conn, "manifest_entries", "title_sort_key", "TEXT")?;
+    ensure_column(conn, "manifest_entries", "group_sort_key", "TEXT")?;
+    ensure_column(conn, "manifest_entries", "title_search_key", "TEXT")?;
+    collation::backfill_keys(conn)
+}
+
+fn add_metadata_columns(conn: &Connection) -> Result<(), String> {
+    let mut added = false;
+    for (column, definition) in [
+        ("aliases", "TEXT"),
+        ("original_title", "TEXT"),
+        ("year", "INTEGER"),
+        ("season", "TEXT"),
+        ("studio", "TEXT"),
+        ("tags", "TEXT"),
+        ("source", "TEXT"),
+        ("rating", "REAL"),
+        ("external_ids", "TEXT"),
+    ] {
+        added |= ensure_column(conn, "manifest_entries", column, definition)?;
+    }
+    if added {
+        reset_manifest_cache(conn)?;
+    }
+    Ok(())
+}
+
+fn create_search_index(conn: &Connection) -> Result<(), String> {
+    search::init_search(conn)
+}
+
+fn create_history_table(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS manifest_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            library_root TEXT NOT NULL,
+            manifest_path TEXT NOT NULL,
+            content TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            saved_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_manifest_history_path ON manifest_history (manifest_path);
+        ",
+    )
+    .map_err(|err| format!("Failed to create tables: {}", err))
+}
+
+fn create_roots_table(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS library_roots (
+            id TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            created_at INTEGER
+        );
+        ",
+    )
+    .map_err(|err| format!("Failed to create tables: {}", err))
+}
+
+fn add_identity_columns(conn: &Connection) -> Result<(), String> {
+    if ensure_column(conn, "manifest_entries", "uid", "TEXT")? {
+        reset_manifest_cache(conn)?;
+    }
+    if ensure_column(conn, "manifest_entries", "fingerprint", "TEXT")? {
+        let ids = {
+            let mut stmt = conn
+                .prepare("SELECT id FROM manifest_entries")
+                .map_err(|err| format!("Failed to prepare query: {}", err))?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|err| format!("Failed to read entries: {}", err))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("Failed to parse entry: {}", err))?
+        };
+        identity::update_fingerprints(conn, &ids)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What 0.2.x created on first launch, before the schema was versioned.
+    const LEGACY_SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS manifest_entries (
+            id TEXT PRIMARY KEY,
+            library_root TEXT NOT NULL,
+            title TEXT,
+            fansub TEXT,
+            subtitle_type TEXT,
+            episodes INTEGER,
+            quality TEXT,
+            note TEXT,
+            path TEXT,
+            folder_name TEXT,
+            group_name TEXT,
+            relative_dir TEXT,
+            updated_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_manifest_library ON manifest_entries (library_root);
+        CREATE TABLE IF NOT EXISTS play_history (
+            entry_id TEXT PRIMARY KEY,
+            last_played_path TEXT,
+            last_played_name TEXT,
+            updated_at INTEGER
+        );
+    ";
+
+    fn has_table(conn: &Connection, name: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?1",
+            [name],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+            > 0
+    }
+
+    fn assert_current(conn: &Connection) {
+        assert_eq!(user_version(conn).unwrap(), SCHEMA_VERSION);
+        for table in [
+            "manifest_entries",
+            "play_history",
+            "manifest_files",
+            "scan_diagnostics",
+            "episode_files",
+            "watch_progress",
+            "manifest_search",
+            "manifest_history",
+            "library_roots",
+        ] {
+            assert!(has_table(conn, table), "missing table {}", table);
+        }
+    }
+
+    #[test]
+    fn fresh_database_reaches_current_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        assert_current(&conn);
+
+        // Running again on a current database is a no-op.
+        migrate(&conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn legacy_database_keeps_entries_and_play_history() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(LEGACY_SCHEMA).unwrap();
+        conn.execute(
+            "
+            INSERT INTO manifest_entries (
+                id, library_root, title, fansub, subtitle_type, episodes, quality, note,
+                path, folder_name, group_name, relative_dir, updated_at
+            ) VALUES (
+                'root::Frieren', 'D:/Anime', '葬送的芙莉莲', 'LoliHouse', '简中', 28, '1080p', '',
+                'D:/Anime/Frieren', 'Frieren', '', 'Frieren', 1700000000
+            )
+            ",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "
+            INSERT INTO play_history (entry_id, last_played_path, last_played_name, updated_at)
+            VALUES ('root::Frieren', 'D:/Anime/Frieren/05.mkv', '05.mkv', 1700000100)
+            ",
+            [],
+        )
+        .unwrap();
+        assert_eq!(user_version(&conn).unwrap(), 0);
+
+        migrate(&conn).unwrap();
+        assert_current(&conn);
+
+        let (title, episodes, title_sort_key) = conn
+            .query_row(
+                "SELECT title, episodes, title_sort_key FROM manifest_entries WHERE id = 'root::Frieren'",
+                [],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, Option<String>>(2)?)),
+            )
+            .unwrap();
+        assert_eq!(title, "葬送的芙莉莲");
+        assert_eq!(episodes, 28);
+        assert!(title_sort_key.is_some(), "sort keys are backfilled");
+
+        let last_played = conn
+            .query_row(
+                "SELECT last_played_path FROM play_history WHERE entry_id = 'root::Frieren'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .unwrap();
+        assert_eq!(last_played, "D:/Anime/Frieren/05.mkv");
+
+        let searchable = conn
+            .query_row("SELECT COUNT(*) FROM manifest_search WHERE entry_id = 'root::Frieren'", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap();
+        assert_eq!(searchable, 1);
+    }
+
+    #[test]
+    fn newer_database_is_rejected() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+
+        assert!(check_supported(SCHEMA_VERSION + 1).is_err());
+        assert!(migrate(&conn).is_err());
+        assert_eq!(user_version(&conn).unwrap(), SCHEMA_VERSION + 1);
+        assert!(!has_table(&conn, "manifest_entries"), "nothing is created in a newer database");
+    }
+
+    #[test]
+    fn resumes_from_intermediate_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..3] {
+            (migration.apply)(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 3).unwrap();
+        conn.execute(
+            "
+            INSERT INTO watch_progress (entry_id, file_path, watched, watch_count, updated_at)
+            VALUES ('root::Frieren', 'D:/Anime/Frieren/01.mkv', 1, 1, 1700000000)
+            ",
+            [],
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        assert_current(&conn);
+        let watched = conn
+            .query_row("SELECT COUNT(*) FROM watch_progress WHERE watched = 1", [], |row| row.get::<_, i64>(0))
+            .unwrap();
+        assert_eq!(watched, 1);
+    }
+}
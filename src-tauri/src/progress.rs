@@ -0,0 +1,254 @@
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::database::Database;
+
+/// The episode an entry should continue with.
+#[derive(Debug, Clone)]
+pub struct NextEpisode {
+    pub path: String,
+    pub file_name: String,
+    pub number: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct EntryProgress {
+    pub watched_episodes: i64,
+    pub next: Option<NextEpisode>,
+}
+
+/// Entry ids bound per progress query, well below SQLite's host parameter limit.
+const PROGRESS_BATCH: usize = 500;
+
+/// Computes watched counts and the next episode for the given entries, so a page of
+/// results doesn't read the episodes of the whole library.
+///
+/// The next episode is the first unwatched regular episode after the last watched one,
+/// falling back to the first unwatched episode when the viewer skipped around.
+pub fn load_entry_progress(
+    conn: &Connection,
+    entry_ids: &[String],
+) -> Result<HashMap<String, EntryProgress>, String> {
+    let mut episodes = HashMap::<String, Vec<(NextEpisode, bool)>>::new();
+    for batch in entry_ids.chunks(PROGRESS_BATCH) {
+        let placeholders = vec!["?"; batch.len()].join(", ");
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "
+                SELECT
+                    e.entry_id,
+                    e.path,
+                    e.file_name,
+                    e.episode_number,
+                    COALESCE(w.watched, 0)
+                FROM episode_files e
+                LEFT JOIN watch_progress w ON w.entry_id = e.entry_id AND w.file_path = e.path
+                WHERE e.entry_id IN ({}) AND e.kind = 'episode'
+                ORDER BY
+                    e.entry_id,
+                    COALESCE(e.season, 1),
+                    e.episode_number IS NULL,
+                    e.episode_number,
+                    e.file_name
+                ",
+                placeholders
+            ))
+            .map_err(|err| format!("Failed to prepare query: {}", err))?;
+
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(batch), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    NextEpisode {
+                        path: row.get(1)?,
+                        file_name: row.get(2)?,
+                        number: row.get(3)?,
+                    },
+                    row.get::<_, bool>(4)?,
+                ))
+            })
+            .map_err(|err| format!("Failed to read watch progress: {}", err))?;
+        for row in rows {
+            let (entry_id, episode, watched) =
+                row.map_err(|err| format!("Failed to parse watch progress: {}", err))?;
+            episodes.entry(entry_id).or_default().push((episode, watched));
+        }
+    }
+
+    let mut progress = HashMap::new();
+    for (entry_id, items) in episodes {
+        // Two releases of the same episode only count once.
+        let mut watched_numbers = HashSet::<u64>::new();
+        let mut watched_unnumbered = 0;
+        for (episode, watched) in &items {
+            match (watched, episode.number) {
+                (true, Some(number)) => {
+                    watched_numbers.insert(number.to_bits());
+                }
+                (true, None) => watched_unnumbered += 1,
+                _ => {}
+            }
+        }
+
+        let last_watched = items.iter().rposition(|(_, watched)| *watched);
+        let next = last_watched
+            .and_then(|index| items[index + 1..].iter().find(|(_, watched)| !*watched))
+            .or_else(|| items.iter().find(|(_, watched)| !*watched))
+            .map(|(episode, _)| episode.clone());
+
+        progress.insert(
+            entry_id,
+            EntryProgress {
+                watched_episodes: watched_numbers.len() as i64 + watched_unnumbered,
+                next,
+            },
+        );
+    }
+
+    Ok(progress)
+}
+
+fn mark_watched(conn: &Connection, entry_id: &str, file_path: &str, now: i64) -> Result<(), String> {
+    conn.execute(
+        "
+        INSERT INTO watch_progress (
+            entry_id,
+            file_path,
+            watched,
+            watch_count,
+            first_watched_at,
+            last_watched_at,
+            position_seconds,
+            updated_at
+        ) VALUES (?1, ?2, 1, 1, ?3, ?3, NULL, ?3)
+        ON CONFLICT(entry_id, file_path) DO UPDATE SET
+            watched = 1,
+            watch_count = watch_count + 1,
+            first_watched_at = COALESCE(first_watched_at, excluded.first_watched_at),
+            last_watched_at = excluded.last_watched_at,
+            position_seconds = NULL,
+            updated_at = excluded.updated_at
+        ",
+        params![entry_id, file_path, now],
+    )
+    .map_err(|err| format!("Failed to update watch progress: {}", err))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn mark_episode_watched(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+    file_path: String,
+    watched: bool,
+) -> Result<(), String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    let now = Utc::now().timestamp();
+    if watched {
+        return mark_watched(&conn, &entry_id, &file_path, now);
+    }
+    conn.execute(
+        "
+        UPDATE watch_progress
+        SET watched = 0, updated_at = ?3
+        WHERE entry_id = ?1 AND file_path = ?2
+        ",
+        params![entry_id, file_path, now],
+    )
+    .map_err(|err| format!("Failed to update watch progress: {}", err))?;
+    Ok(())
+}
+
+/// Marks every regular episode numbered up to `episode` as watched.
+///
+/// Episodes that are already watched keep their watch count.
+#[tauri::command]
+pub fn mark_episodes_watched_through(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+    episode: f64,
+) -> Result<usize, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    let now = Utc::now().timestamp();
+    let changed = conn
+        .execute(
+            "
+            INSERT INTO watch_progress (
+                entry_id,
+                file_path,
+                watched,
+                watch_count,
+                first_watched_at,
+                last_watched_at,
+                position_seconds,
+                updated_at
+            )
+            SELECT entry_id, path, 1, 1, ?3, ?3, NULL, ?3
+            FROM episode_files
+            WHERE entry_id = ?1 AND kind = 'episode' AND episode_number <= ?2
+            ON CONFLICT(entry_id, file_path) DO UPDATE SET
+                watched = 1,
+                watch_count = MAX(watch_count, 1),
+                first_watched_at = COALESCE(first_watched_at, excluded.first_watched_at),
+                last_watched_at = excluded.last_watched_at,
+                position_seconds = NULL,
+                updated_at = excluded.updated_at
+            WHERE watch_progress.watched = 0
+            ",
+            params![entry_id, episode, now],
+        )
+        .map_err(|err| format!("Failed to update watch progress: {}", err))?;
+    Ok(changed)
+}
+
+/// Clears progress for one episode, or for the whole entry when `file_path` is omitted.
+#[tauri::command]
+pub fn reset_watch_progress(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+    file_path: Option<String>,
+) -> Result<(), String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    match file_path {
+        Some(file_path) => conn.execute(
+            "DELETE FROM watch_progress WHERE entry_id = ?1 AND file_path = ?2",
+            params![entry_id, file_path],
+        ),
+        None => conn.execute("DELETE FROM watch_progress WHERE entry_id = ?1", params![entry_id]),
+    }
+    .map_err(|err| format!("Failed to reset watch progress: {}", err))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_playback_position(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    entry_id: String,
+    file_path: String,
+    position_seconds: f64,
+) -> Result<(), String> {
+    let base = PathBuf::from(base_dir.trim());
+    let conn = database.open(&base)?;
+    let now = Utc::now().timestamp();
+    conn.execute(
+        "
+        INSERT INTO watch_progress (entry_id, file_path, watched, watch_count, position_seconds, updated_at)
+        VALUES (?1, ?2, 0, 0, ?3, ?4)
+        ON CONFLICT(entry_id, file_path) DO UPDATE SET
+            position_seconds = excluded.position_seconds,
+            updated_at = excluded.updated_at
+        ",
+        params![entry_id, file_path, position_seconds, now],
+    )
+    .map_err(|err| format!("Failed to update playback position: {}", err))?;
+    Ok(())
+}
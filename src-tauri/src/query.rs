@@ -0,0 +1,210 @@
+use rusqlite::types::Value;
+use rusqlite::params_from_iter;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::database::Database;
+use crate::{collect_entries, normalize_path, read_entry, LibraryEntry, ENTRY_SELECT};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryStatus {
+    /// A positive episode count was declared.
+    Finished,
+    /// `episodes: -1` in the manifest.
+    Ongoing,
+    /// No episode count was declared.
+    Unknown,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LibraryFilter {
+    fansubs: Vec<String>,
+    qualities: Vec<String>,
+    groups: Vec<String>,
+    status: Vec<EntryStatus>,
+    has_unwatched: Option<bool>,
+    /// Total size of the entry's video files, in bytes.
+    min_size: Option<i64>,
+    max_size: Option<i64>,
+    /// Unix timestamp in seconds.
+    added_after: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortKey {
+    Title,
+    LastPlayed,
+    Added,
+    Size,
+    /// The episode count the manifest declares, -1 for ongoing series.
+    Episodes,
+    /// Episode files found on disk.
+    Available,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortOrder {
+    key: SortKey,
+    #[serde(default)]
+    descending: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LibraryQuery {
+    filter: LibraryFilter,
+    sort: Vec<SortOrder>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryPage {
+    entries: Vec<LibraryEntry>,
+    /// Number of entries matching the filter, ignoring pagination.
+    total: usize,
+    offset: usize,
+    limit: usize,
+}
+
+/// Appends `column IN (?, ...)` for a non-empty set of values.
+fn push_in(conditions: &mut Vec<String>, values: &mut Vec<Value>, column: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    let placeholders = vec!["?"; items.len()].join(", ");
+    conditions.push(format!("{} IN ({})", column, placeholders));
+    values.extend(items.iter().map(|item| Value::Text(item.clone())));
+}
+
+fn build_conditions(filter: &LibraryFilter, values: &mut Vec<Value>) -> Vec<String> {
+    let mut conditions = vec!["m.library_root = ?".to_string()];
+
+    push_in(&mut conditions, values, "m.fansub", &filter.fansubs);
+    push_in(&mut conditions, values, "m.quality", &filter.qualities);
+    push_in(&mut conditions, values, "m.group_name", &filter.groups);
+
+    if !filter.status.is_empty() {
+        let statuses = filter
+            .status
+            .iter()
+            .map(|status| match status {
+                EntryStatus::Finished => "CAST(m.episodes AS INTEGER) > 0",
+                EntryStatus::Ongoing => "CAST(m.episodes AS INTEGER) = -1",
+                EntryStatus::Unknown => "COALESCE(CAST(m.episodes AS INTEGER), 0) = 0",
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        conditions.push(format!("({})", statuses));
+    }
+
+    if let Some(has_unwatched) = filter.has_unwatched {
+        let unwatched = "
+            EXISTS (
+                SELECT 1
+                FROM episode_files f
+                LEFT JOIN watch_progress w ON w.entry_id = f.entry_id AND w.file_path = f.path
+                WHERE f.entry_id = m.id AND f.kind = 'episode' AND COALESCE(w.watched, 0) = 0
+            )";
+        if has_unwatched {
+            conditions.push(unwatched.to_string());
+        } else {
+            conditions.push(format!("NOT {}", unwatched));
+        }
+    }
+
+    if let Some(min_size) = filter.min_size {
+        conditions.push("COALESCE(e.total_size, 0) >= ?".to_string());
+        values.push(Value::Integer(min_size));
+    }
+    if let Some(max_size) = filter.max_size {
+        conditions.push("COALESCE(e.total_size, 0) <= ?".to_string());
+        values.push(Value::Integer(max_size));
+    }
+    if let Some(added_after) = filter.added_after {
+        conditions.push("COALESCE(m.added_at, m.updated_at, 0) >= ?".to_string());
+        values.push(Value::Integer(added_after));
+    }
+
+    conditions
+}
+
+fn order_clause(sort: &[SortOrder]) -> String {
+    let mut terms = sort
+        .iter()
+        .map(|order| {
+            let column = match order.key {
+                SortKey::Title => "COALESCE(m.title_sort_key, m.title)",
+                SortKey::LastPlayed => "COALESCE(p.updated_at, 0)",
+                SortKey::Added => "COALESCE(m.added_at, m.updated_at, 0)",
+                SortKey::Size => "COALESCE(e.total_size, 0)",
+                SortKey::Episodes => "COALESCE(CAST(m.episodes AS INTEGER), 0)",
+                SortKey::Available => "COALESCE(e.available, 0)",
+            };
+            format!("{} {}", column, if order.descending { "DESC" } else { "ASC" })
+        })
+        .collect::<Vec<_>>();
+    if terms.is_empty() {
+        terms.push("m.group_sort_key".to_string());
+        terms.push("m.title_sort_key".to_string());
+    }
+    // Keeps pages stable when the sort keys tie.
+    terms.push("m.id".to_string());
+    terms.join(", ")
+}
+
+/// Filters, sorts and pages the library in SQL.
+#[tauri::command]
+pub fn query_library(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    query: LibraryQuery,
+) -> Result<LibraryPage, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let library_root = normalize_path(&base);
+    let conn = database.open(&base)?;
+
+    let mut values = vec![Value::Text(library_root.clone())];
+    let conditions = build_conditions(&query.filter, &mut values);
+    let where_clause = conditions.join(" AND ");
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    let total = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM ({} WHERE {})", ENTRY_SELECT, where_clause),
+            params_from_iter(values.iter()),
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|err| format!("Failed to count entries: {}", err))?;
+
+    let sql = format!(
+        "{} WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+        ENTRY_SELECT,
+        where_clause,
+        order_clause(&query.sort)
+    );
+    values.push(Value::Integer(limit as i64));
+    values.push(Value::Integer(query.offset as i64));
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|err| format!("Failed to prepare query: {}", err))?;
+    let rows = stmt
+        .query_map(params_from_iter(values.iter()), read_entry)
+        .map_err(|err| format!("Failed to read entries: {}", err))?;
+    let entries = collect_entries(&conn, rows)?;
+
+    Ok(LibraryPage {
+        entries,
+        total: total as usize,
+        offset: query.offset,
+        limit,
+    })
+}
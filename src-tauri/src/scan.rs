@@ -0,0 +1,337 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::database::Database;
+use crate::{libraries, normalize_path, refresh_index, RefreshResult};
+
+/// Minimum gap between two `scan-progress` events for the same scan.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanProgressEvent {
+    scan_id: String,
+    base_dir: String,
+    directories_visited: usize,
+    manifests_found: usize,
+    current_path: String,
+    elapsed_ms: u64,
+    /// Estimated time left, once there is a previous scan to compare against.
+    eta_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanCompletedEvent {
+    scan_id: String,
+    base_dir: String,
+    result: RefreshResult,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanFinishedEvent {
+    scan_id: String,
+    base_dir: String,
+    message: String,
+}
+
+/// Whether a scan was cancelled, shared by the scan and `cancel_scan`. Both transitions
+/// happen under one lock, so a cancel either stops the scan before it writes the index or
+/// reports that it came too late.
+#[derive(Default)]
+pub struct ScanControl {
+    cancelled: AtomicBool,
+    /// Set once the scan started writing the index, when cancelling no longer has an effect.
+    writing: Mutex<bool>,
+}
+
+impl ScanControl {
+    fn lock_writing(&self) -> Result<std::sync::MutexGuard<'_, bool>, String> {
+        self.writing
+            .lock()
+            .map_err(|_| "Scan state is poisoned".to_string())
+    }
+
+    /// Asks the scan to stop. Returns false when it is already writing its results.
+    pub fn cancel(&self) -> Result<bool, String> {
+        let writing = self.lock_writing()?;
+        if *writing {
+            return Ok(false);
+        }
+        self.cancelled.store(true, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Fails if the scan was cancelled, and otherwise makes later cancels return false.
+    pub fn begin_write(&self) -> Result<(), String> {
+        let mut writing = self.lock_writing()?;
+        if self.is_cancelled() {
+            return Err("扫描已取消".to_string());
+        }
+        *writing = true;
+        Ok(())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+struct ActiveScan {
+    base_dir: String,
+    control: Arc<ScanControl>,
+}
+
+/// Tauri-managed state tracking the scans running in the background.
+#[derive(Default)]
+pub struct ScanManager {
+    next_id: AtomicU64,
+    active: Mutex<HashMap<String, ActiveScan>>,
+}
+
+impl ScanManager {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, ActiveScan>>, String> {
+        self.active
+            .lock()
+            .map_err(|_| "Scan state is poisoned".to_string())
+    }
+
+    /// Whether any library is being scanned in the background.
+    pub fn is_scanning(&self) -> Result<bool, String> {
+        Ok(!self.lock()?.is_empty())
+    }
+}
+
+/// Counts what a scan has walked so far, emits throttled progress events and tells the
+/// walk when it has been cancelled.
+pub struct ScanReporter {
+    scan_id: String,
+    base_dir: String,
+    control: Arc<ScanControl>,
+    emit: Box<dyn FnMut(ScanProgressEvent) + Send>,
+    started: Instant,
+    last_emitted: Option<Instant>,
+    directories_visited: usize,
+    manifests_found: usize,
+    /// Manifests the index held before this scan, used to estimate how far along it is.
+    expected_manifests: usize,
+}
+
+impl ScanReporter {
+    /// A reporter for scan `scan_id` that hands each progress event to `emit`.
+    pub fn new(
+        scan_id: String,
+        base_dir: String,
+        control: Arc<ScanControl>,
+        emit: impl FnMut(ScanProgressEvent) + Send + 'static,
+    ) -> Self {
+        Self {
+            scan_id,
+            base_dir,
+            control,
+            emit: Box::new(emit),
+            started: Instant::now(),
+            last_emitted: None,
+            directories_visited: 0,
+            manifests_found: 0,
+            expected_manifests: 0,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    /// Set by `cancel_scan`; shared with the worker threads reading manifests.
+    pub fn cancelled_flag(&self) -> &AtomicBool {
+        &self.control.cancelled
+    }
+
+    /// Fails with a cancellation error once `cancel_scan` was called for this scan.
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err("扫描已取消".to_string());
+        }
+        Ok(())
+    }
+
+    /// Called right before the index is written. Fails if the scan was cancelled, and
+    /// otherwise makes later `cancel_scan` calls report that it is too late to stop.
+    pub fn begin_write(&self) -> Result<(), String> {
+        self.control.begin_write()
+    }
+
+    pub fn set_expected_manifests(&mut self, count: usize) {
+        self.expected_manifests = count;
+    }
+
+    pub fn visit_directory(&mut self, path: &Path) {
+        self.directories_visited += 1;
+        self.report(path, false);
+    }
+
+    pub fn found_manifest(&mut self, path: &Path) {
+        self.manifests_found += 1;
+        self.report(path, false);
+    }
+
+    /// Emits the final counts before the index is written.
+    pub fn finish_walk(&mut self, base: &Path) {
+        self.report(base, true);
+    }
+
+    fn eta_ms(&self, elapsed: Duration) -> Option<u64> {
+        if self.manifests_found == 0 || self.expected_manifests <= self.manifests_found {
+            return None;
+        }
+        let remaining = (self.expected_manifests - self.manifests_found) as f64 / self.manifests_found as f64;
+        Some((elapsed.as_millis() as f64 * remaining) as u64)
+    }
+
+    fn report(&mut self, path: &Path, force: bool) {
+        let now = Instant::now();
+        if !force
+            && self
+                .last_emitted
+                .map(|last| now.duration_since(last) < PROGRESS_INTERVAL)
+                .unwrap_or(false)
+        {
+            return;
+        }
+        self.last_emitted = Some(now);
+        let elapsed = now.duration_since(self.started);
+        let event = ScanProgressEvent {
+            scan_id: self.scan_id.clone(),
+            base_dir: self.base_dir.clone(),
+            directories_visited: self.directories_visited,
+            manifests_found: self.manifests_found,
+            current_path: normalize_path(path),
+            elapsed_ms: elapsed.as_millis() as u64,
+            eta_ms: if force { None } else { self.eta_ms(elapsed) },
+        };
+        (self.emit)(event);
+    }
+}
+
+fn run_scan(app: AppHandle, mut reporter: ScanReporter, base: PathBuf, full_rescan: bool) {
+    let result = {
+        let database = app.state::<Database>();
+        let settings = libraries::scan_settings(&app, &base);
+        refresh_index(&database, &base, &[], full_rescan, &settings, Some(&mut reporter))
+    };
+
+    let scans = app.state::<ScanManager>();
+    if let Ok(mut active) = scans.lock() {
+        active.remove(&reporter.scan_id);
+    }
+
+    let scan_id = reporter.scan_id.clone();
+    let base_dir = reporter.base_dir.clone();
+    let _ = match result {
+        Ok(result) => app.emit(
+            "scan-completed",
+            ScanCompletedEvent {
+                scan_id,
+                base_dir,
+                result,
+            },
+        ),
+        Err(message) if reporter.is_cancelled() => app.emit(
+            "scan-cancelled",
+            ScanFinishedEvent {
+                scan_id,
+                base_dir,
+                message,
+            },
+        ),
+        Err(message) => app.emit(
+            "scan-failed",
+            ScanFinishedEvent {
+                scan_id,
+                base_dir,
+                message,
+            },
+        ),
+    };
+}
+
+/// Starts rescanning `base_dir` in the background and returns the scan id right away.
+///
+/// Progress arrives as `scan-progress` events and the outcome as one of `scan-completed`,
+/// `scan-cancelled` or `scan-failed`. Asking again while the library is still being
+/// scanned returns the running scan's id instead of starting a second walk.
+#[tauri::command]
+pub fn refresh_library(
+    app: AppHandle,
+    scans: tauri::State<'_, ScanManager>,
+    base_dir: String,
+    full_rescan: Option<bool>,
+) -> Result<String, String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+    let library_root = normalize_path(&base);
+
+    let mut active = scans.lock()?;
+    if let Some((id, _)) = active.iter().find(|(_, scan)| scan.base_dir == library_root) {
+        return Ok(id.clone());
+    }
+    let scan_id = format!("scan-{}", scans.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+    let control = Arc::new(ScanControl::default());
+    active.insert(
+        scan_id.clone(),
+        ActiveScan {
+            base_dir: library_root.clone(),
+            control: control.clone(),
+        },
+    );
+    drop(active);
+
+    let events = app.clone();
+    let reporter = ScanReporter::new(scan_id.clone(), library_root, control, move |event| {
+        let _ = events.emit("scan-progress", event);
+    });
+    let full_rescan = full_rescan.unwrap_or(false);
+    thread::spawn(move || run_scan(app, reporter, base, full_rescan));
+    Ok(scan_id)
+}
+
+/// Stops a running scan before it writes anything; the index keeps its previous state.
+/// Returns false when the scan already finished or is already writing its results.
+#[tauri::command]
+pub fn cancel_scan(scans: tauri::State<'_, ScanManager>, scan_id: String) -> Result<bool, String> {
+    let active = scans.lock()?;
+    match active.get(&scan_id) {
+        Some(scan) => scan.control.cancel(),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_before_writing_stops_the_scan() {
+        let control = ScanControl::default();
+        assert!(control.cancel().unwrap());
+        assert!(control.is_cancelled());
+        assert!(control.begin_write().is_err());
+    }
+
+    #[test]
+    fn cancel_after_writing_started_is_refused() {
+        let control = ScanControl::default();
+        control.begin_write().unwrap();
+        assert!(!control.cancel().unwrap());
+        assert!(!control.is_cancelled());
+    }
+}
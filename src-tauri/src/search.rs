@@ -0,0 +1,380 @@
+use rusqlite::{params, params_from_iter, Connection};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::database::Database;
+use crate::normalize_path;
+
+const HIGHLIGHT_OPEN: &str = "<mark>";
+const HIGHLIGHT_CLOSE: &str = "</mark>";
+/// Control characters FTS5 puts around matches in place of the tags, so the text can be
+/// escaped before the real markers go in.
+const MATCH_OPEN: char = '\u{2}';
+const MATCH_CLOSE: char = '\u{3}';
+const DEFAULT_LIMIT: usize = 50;
+
+/// The trigram tokenizer only indexes runs of three or more characters, so shorter terms
+/// (most partial CJK input) go through a slower substring scan instead.
+const MIN_INDEXED_TERM: usize = 3;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    entry_id: String,
+    title: String,
+    /// HTML-escaped title with matches wrapped in `<mark>`; the snippet is escaped the same way.
+    title_highlight: String,
+    snippet: String,
+    rank: f64,
+}
+
+/// Creates the FTS index, fills it from `manifest_entries` and keeps it in sync through
+/// triggers. Any earlier version of the index is dropped first.
+///
+/// `title_romanized` holds the pinyin/romaji search key, so `zsdfll` or `zangsong` find
+/// "葬送的芙莉莲", and `aliases` holds the original title and manifest aliases.
+pub fn init_search(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        DROP TRIGGER IF EXISTS manifest_search_insert;
+        DROP TRIGGER IF EXISTS manifest_search_update;
+        DROP TRIGGER IF EXISTS manifest_search_delete;
+        DROP TABLE IF EXISTS manifest_search;
+        ",
+    )
+    .map_err(|err| format!("Failed to rebuild search index: {}", err))?;
+
+    conn.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS manifest_search USING fts5(
+            entry_id UNINDEXED,
+            library_root UNINDEXED,
+            title,
+            folder_name,
+            fansub,
+            note,
+            group_name,
+            aliases,
+            title_romanized,
+            tokenize = 'trigram'
+        );
+        CREATE TRIGGER IF NOT EXISTS manifest_search_insert AFTER INSERT ON manifest_entries BEGIN
+            INSERT INTO manifest_search (entry_id, library_root, title, folder_name, fansub, note, group_name, aliases, title_romanized)
+            VALUES (
+                new.id,
+                new.library_root,
+                new.title,
+                new.folder_name,
+                new.fansub,
+                new.note,
+                new.group_name,
+                TRIM(COALESCE(new.original_title, '') || ' ' || COALESCE((SELECT group_concat(value, ' ') FROM json_each(new.aliases)), '')),
+                new.title_search_key
+            );
+        END;
+        CREATE TRIGGER IF NOT EXISTS manifest_search_update AFTER UPDATE ON manifest_entries BEGIN
+            DELETE FROM manifest_search WHERE entry_id = old.id;
+            INSERT INTO manifest_search (entry_id, library_root, title, folder_name, fansub, note, group_name, aliases, title_romanized)
+            VALUES (
+                new.id,
+                new.library_root,
+                new.title,
+                new.folder_name,
+                new.fansub,
+                new.note,
+                new.group_name,
+                TRIM(COALESCE(new.original_title, '') || ' ' || COALESCE((SELECT group_concat(value, ' ') FROM json_each(new.aliases)), '')),
+                new.title_search_key
+            );
+        END;
+        CREATE TRIGGER IF NOT EXISTS manifest_search_delete AFTER DELETE ON manifest_entries BEGIN
+            DELETE FROM manifest_search WHERE entry_id = old.id;
+        END;
+        ",
+    )
+    .map_err(|err| format!("Failed to init search index: {}", err))?;
+
+    conn.execute_batch(
+        "
+        INSERT INTO manifest_search (entry_id, library_root, title, folder_name, fansub, note, group_name, aliases, title_romanized)
+        SELECT
+            m.id,
+            m.library_root,
+            m.title,
+            m.folder_name,
+            m.fansub,
+            m.note,
+            m.group_name,
+            TRIM(COALESCE(m.original_title, '') || ' ' || COALESCE((SELECT group_concat(value, ' ') FROM json_each(m.aliases)), '')),
+            m.title_search_key
+        FROM manifest_entries m;
+        ",
+    )
+    .map_err(|err| format!("Failed to build search index: {}", err))?;
+
+    Ok(())
+}
+
+fn quote_term(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len() + 2);
+    escaped.push('%');
+    for ch in term.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped.push('%');
+    escaped
+}
+
+/// Appends `ch` to `out`, escaped for use as HTML text or attribute content.
+fn push_escaped(out: &mut String, ch: char) {
+    match ch {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        '\'' => out.push_str("&#39;"),
+        _ => out.push(ch),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        push_escaped(&mut escaped, ch);
+    }
+    escaped
+}
+
+/// Escapes FTS5 highlight output and turns its match delimiters into highlight markers.
+fn mark_matches(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            MATCH_OPEN => result.push_str(HIGHLIGHT_OPEN),
+            MATCH_CLOSE => result.push_str(HIGHLIGHT_CLOSE),
+            _ => push_escaped(&mut result, ch),
+        }
+    }
+    result
+}
+
+/// HTML-escapes `text` and wraps case-insensitive occurrences of `terms` in highlight markers.
+fn highlight_terms(text: &str, terms: &[&str]) -> String {
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths for some scripts; fall back to no highlight then.
+    if lower.len() != text.len() {
+        return escape_html(text);
+    }
+    let mut marked = vec![false; text.len()];
+    for term in terms {
+        let needle = term.to_lowercase();
+        if needle.is_empty() {
+            continue;
+        }
+        for (start, _) in lower.match_indices(&needle) {
+            for flag in &mut marked[start..start + needle.len()] {
+                *flag = true;
+            }
+        }
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut open = false;
+    for (index, ch) in text.char_indices() {
+        if marked[index] && !open {
+            result.push_str(HIGHLIGHT_OPEN);
+            open = true;
+        } else if !marked[index] && open {
+            result.push_str(HIGHLIGHT_CLOSE);
+            open = false;
+        }
+        push_escaped(&mut result, ch);
+    }
+    if open {
+        result.push_str(HIGHLIGHT_CLOSE);
+    }
+    result
+}
+
+fn search_indexed(
+    conn: &Connection,
+    library_root: &str,
+    terms: &[&str],
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    let query = terms.iter().map(|term| quote_term(term)).collect::<Vec<_>>().join(" ");
+    let mut stmt = conn
+        .prepare(
+            "
+            SELECT
+                entry_id,
+                title,
+                highlight(manifest_search, 2, ?3, ?4),
+                snippet(manifest_search, -1, ?3, ?4, '…', 16),
+                bm25(manifest_search, 0.0, 0.0, 10.0, 4.0, 1.0, 1.0, 2.0, 8.0, 6.0) AS score
+            FROM manifest_search
+            WHERE manifest_search MATCH ?1 AND library_root = ?2
+            ORDER BY score
+            LIMIT ?5
+            ",
+        )
+        .map_err(|err| format!("Failed to prepare search: {}", err))?;
+
+    let rows = stmt
+        .query_map(
+            params![
+                query,
+                library_root,
+                MATCH_OPEN.to_string(),
+                MATCH_CLOSE.to_string(),
+                limit as i64
+            ],
+            |row| {
+                Ok(SearchHit {
+                    entry_id: row.get(0)?,
+                    title: row.get(1)?,
+                    title_highlight: mark_matches(&row.get::<_, String>(2)?),
+                    snippet: mark_matches(&row.get::<_, String>(3)?),
+                    rank: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|err| format!("Failed to search library: {}", err))?;
+
+    let mut hits = Vec::new();
+    for hit in rows {
+        hits.push(hit.map_err(|err| format!("Failed to read search result: {}", err))?);
+    }
+    Ok(hits)
+}
+
+fn search_substring(
+    conn: &Connection,
+    library_root: &str,
+    terms: &[&str],
+    limit: usize,
+) -> Result<Vec<SearchHit>, String> {
+    const COLUMNS: [&str; 7] = [
+        "title",
+        "aliases",
+        "folder_name",
+        "group_name",
+        "fansub",
+        "note",
+        "title_romanized",
+    ];
+
+    let mut conditions = Vec::new();
+    let mut values = vec![library_root.to_string()];
+    for term in terms {
+        values.push(escape_like(term));
+        let index = values.len();
+        let any_column = COLUMNS
+            .iter()
+            .map(|column| format!("{} LIKE ?{} ESCAPE '\\'", column, index))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        conditions.push(format!("({})", any_column));
+    }
+    let sql = format!(
+        "
+        SELECT entry_id, title, folder_name, group_name, fansub, note, aliases, title_romanized
+        FROM manifest_search
+        WHERE library_root = ?1 AND {}
+        ",
+        conditions.join(" AND ")
+    );
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|err| format!("Failed to prepare search: {}", err))?;
+    let rows = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            let mut fields = Vec::with_capacity(8);
+            for index in 0..8 {
+                fields.push(row.get::<_, Option<String>>(index)?.unwrap_or_default());
+            }
+            Ok(fields)
+        })
+        .map_err(|err| format!("Failed to search library: {}", err))?;
+
+    let mut hits = Vec::new();
+    for row in rows {
+        let fields = row.map_err(|err| format!("Failed to read search result: {}", err))?;
+        let title = &fields[1];
+        let lower_title = title.to_lowercase();
+        let romanized = &fields[7];
+        let title_matches = terms
+            .iter()
+            .filter(|term| {
+                let term = term.to_lowercase();
+                lower_title.contains(&term) || romanized.contains(&term)
+            })
+            .count();
+        // The romanized key is only for matching; it is never shown as a snippet.
+        let snippet = fields[1..7]
+            .iter()
+            .find(|value| {
+                let value = value.to_lowercase();
+                terms.iter().any(|term| value.contains(&term.to_lowercase()))
+            })
+            .map(|value| highlight_terms(value, terms))
+            .unwrap_or_default();
+        hits.push(SearchHit {
+            entry_id: fields[0].clone(),
+            title: title.clone(),
+            title_highlight: highlight_terms(title, terms),
+            snippet,
+            // Lower is better, matching bm25: title hits first, then shorter titles.
+            rank: -(title_matches as f64) + title.chars().count() as f64 / 1000.0,
+        });
+    }
+    hits.sort_by(|a, b| a.rank.total_cmp(&b.rank));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+#[tauri::command]
+pub fn search_library(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let library_root = normalize_path(&base);
+    let conn = database.open(&base)?;
+    let terms = query.split_whitespace().collect::<Vec<&str>>();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+
+    if terms.iter().all(|term| term.chars().count() >= MIN_INDEXED_TERM) {
+        search_indexed(&conn, &library_root, &terms, limit)
+    } else {
+        search_substring(&conn, &library_root, &terms, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_escape_the_text() {
+        assert_eq!(
+            highlight_terms("<img src=x onerror=alert(1)> Frieren", &["frieren"]),
+            "&lt;img src=x onerror=alert(1)&gt; <mark>Frieren</mark>"
+        );
+        assert_eq!(highlight_terms("A & B", &["c"]), "A &amp; B");
+        assert_eq!(mark_matches("\u{2}<b>\u{3} & \"x\""), "<mark>&lt;b&gt;</mark> &amp; &quot;x&quot;");
+    }
+}
@@ -0,0 +1,357 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::database::Database;
+use crate::history::write_atomic;
+use crate::scan::ScanManager;
+use crate::{libraries, migrations, normalize_path, DB_FILE_NAME};
+
+const SETTINGS_FILE_NAME: &str = "storage.json";
+
+/// Tables copied from an in-library database; the search index is rebuilt by the triggers
+/// on `manifest_entries`.
+const MIGRATED_TABLES: [&str; 7] = [
+    "manifest_entries",
+    "manifest_files",
+    "manifest_history",
+    "scan_diagnostics",
+    "episode_files",
+    "watch_progress",
+    "play_history",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StorageSettings {
+    /// `None` keeps the database in the app data directory.
+    database_dir: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseLocation {
+    path: String,
+    directory: String,
+    default_directory: String,
+    is_default: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseMigration {
+    entries: usize,
+    play_history: usize,
+    watch_progress: usize,
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|err| format!("Failed to resolve config directory: {}", err))?;
+    Ok(dir.join(SETTINGS_FILE_NAME))
+}
+
+fn load_settings(app: &AppHandle) -> Result<StorageSettings, String> {
+    let path = settings_path(app)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(StorageSettings::default()),
+        Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+    };
+    serde_json::from_str(&content).map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+}
+
+fn save_settings(app: &AppHandle, settings: &StorageSettings) -> Result<(), String> {
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create {}: {}", parent.display(), err))?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|err| format!("Failed to serialize storage settings: {}", err))?;
+    write_atomic(&path, content.as_bytes())
+}
+
+fn default_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|err| format!("Failed to resolve data directory: {}", err))
+}
+
+fn resolve_dir(app: &AppHandle, settings: &StorageSettings) -> Result<PathBuf, String> {
+    match settings.database_dir.as_deref().map(str::trim) {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => default_dir(app),
+    }
+}
+
+/// Resolves the database location from the saved settings and starts managing the
+/// connection pool. Called once from `setup`.
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let dir = resolve_dir(app, &load_settings(app)?)?;
+    fs::create_dir_all(&dir).map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    app.manage(Database::new(dir.join(DB_FILE_NAME)));
+    Ok(())
+}
+
+/// Where earlier versions kept the index: inside the library root itself.
+fn legacy_database_path(base: &Path) -> PathBuf {
+    base.join(DB_FILE_NAME)
+}
+
+/// The database plus the journal files SQLite keeps next to it.
+fn database_files(path: &Path) -> Vec<PathBuf> {
+    let name = path.to_string_lossy();
+    ["", "-wal", "-shm", "-journal"]
+        .iter()
+        .map(|suffix| PathBuf::from(format!("{}{}", name, suffix)))
+        .collect()
+}
+
+/// Moves each existing file in `from` to the matching path in `to`. When one fails, the
+/// files already moved are put back so the database isn't left split across two places.
+fn move_database_files(from: &Path, to: &Path) -> Result<(), String> {
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for (source, target) in database_files(from).into_iter().zip(database_files(to)) {
+        if !source.exists() {
+            continue;
+        }
+        if let Err(err) = move_file(&source, &target) {
+            for (source, target) in moved.iter().rev() {
+                let _ = move_file(target, source);
+            }
+            return Err(err);
+        }
+        moved.push((source, target));
+    }
+    Ok(())
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // Renaming fails across drives; copy, then remove the original.
+    fs::copy(from, to).map_err(|err| format!("Failed to copy {}: {}", from.display(), err))?;
+    fs::remove_file(from).map_err(|err| format!("Failed to remove {}: {}", from.display(), err))
+}
+
+fn location(app: &AppHandle, settings: &StorageSettings) -> Result<DatabaseLocation, String> {
+    let directory = resolve_dir(app, settings)?;
+    let default_directory = default_dir(app)?;
+    Ok(DatabaseLocation {
+        path: normalize_path(&directory.join(DB_FILE_NAME)),
+        is_default: directory == default_directory,
+        directory: normalize_path(&directory),
+        default_directory: normalize_path(&default_directory),
+    })
+}
+
+#[tauri::command]
+pub fn get_database_location(app: AppHandle) -> Result<DatabaseLocation, String> {
+    location(&app, &load_settings(&app)?)
+}
+
+/// Moves the index database to `directory`, or back to the app data directory when it
+/// is omitted. A database already present at the target is used as-is. Refused while a
+/// scan or any other database work is running.
+#[tauri::command]
+pub fn set_database_location(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    scans: tauri::State<'_, ScanManager>,
+    directory: Option<String>,
+) -> Result<DatabaseLocation, String> {
+    let settings = StorageSettings {
+        database_dir: directory
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty()),
+    };
+    let target_dir = resolve_dir(&app, &settings)?;
+    fs::create_dir_all(&target_dir)
+        .map_err(|err| format!("Failed to create {}: {}", target_dir.display(), err))?;
+
+    let current = database.path()?;
+    let target = target_dir.join(DB_FILE_NAME);
+    if current == target {
+        save_settings(&app, &settings)?;
+        return location(&app, &settings);
+    }
+
+    if scans.is_scanning()? {
+        return Err("资料库正在扫描，请等扫描结束后再移动数据库".to_string());
+    }
+    database.relocate(target, |current, target| {
+        // A database already at the target is used as-is.
+        if !current.exists() || target.exists() {
+            return Ok(());
+        }
+        // Fold the write-ahead log into the main file so it moves as one piece.
+        Connection::open(current)
+            .and_then(|conn| conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);"))
+            .map_err(|err| format!("Failed to checkpoint database: {}", err))?;
+        move_database_files(current, target)
+    })?;
+
+    save_settings(&app, &settings)?;
+    location(&app, &settings)
+}
+
+/// Whether `base_dir` still holds an index database from before it moved out of the library.
+#[tauri::command]
+pub fn has_legacy_database(base_dir: String) -> bool {
+    legacy_database_path(Path::new(base_dir.trim())).is_file()
+}
+
+fn column_names(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA {}.table_info({})", schema, table))
+        .map_err(|err| format!("Failed to inspect {}: {}", table, err))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|err| format!("Failed to inspect {}: {}", table, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Failed to inspect {}: {}", table, err))?;
+    Ok(columns)
+}
+
+fn count_rows(conn: &Connection, table: &str, root_id: &str) -> Result<usize, String> {
+    let column = if table == "manifest_entries" { "id" } else { "entry_id" };
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM legacy.{} WHERE substr({}, 1, length(?1)) = ?1",
+            table, column
+        ),
+        [format!("{}::", root_id)],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count as usize)
+    .map_err(|err| format!("Failed to count {}: {}", table, err))
+}
+
+/// Copies the legacy database and its write-ahead log to a temporary file, so upgrading
+/// it never writes into the library folder, which may be a read-only share.
+fn copy_legacy_database(legacy_path: &Path) -> Result<PathBuf, String> {
+    let copy = std::env::temp_dir().join(format!("anime-manager-legacy-{}.sqlite", uuid::Uuid::new_v4()));
+    for (source, target) in database_files(legacy_path).iter().zip(database_files(&copy)).take(2) {
+        if source.exists() {
+            if let Err(err) = fs::copy(source, &target) {
+                remove_database_files(&copy);
+                return Err(format!("Failed to copy {}: {}", source.display(), err));
+            }
+        }
+    }
+    Ok(copy)
+}
+
+fn remove_database_files(path: &Path) {
+    for file in database_files(path) {
+        if file.exists() {
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+/// Copies the index database an older version kept inside `base_dir` into the configured
+/// location, keeping play history, watch progress and manifest history. Whatever the new
+/// database already indexed for this root is replaced. The old file is left untouched;
+/// `remove_legacy_database` deletes it once the user asks to.
+#[tauri::command]
+pub fn migrate_legacy_database(
+    database: tauri::State<'_, Database>,
+    base_dir: String,
+) -> Result<DatabaseMigration, String> {
+    let base = PathBuf::from(base_dir.trim());
+    let legacy_path = legacy_database_path(&base);
+    if !legacy_path.is_file() {
+        return Err(format!("{} 中没有需要迁移的数据库", base.display()));
+    }
+    let copy = copy_legacy_database(&legacy_path)?;
+    let result = migrate_copy(&database, &base, &copy);
+    remove_database_files(&copy);
+    result
+}
+
+fn migrate_copy(database: &Database, base: &Path, copy: &Path) -> Result<DatabaseMigration, String> {
+    let library_root = normalize_path(base);
+
+    // Bring the copy up to the current schema and root ids before copying rows.
+    let root_id = {
+        let legacy = Connection::open(copy).map_err(|err| format!("Failed to open database: {}", err))?;
+        migrations::migrate(&legacy)?;
+        libraries::adopt_root(&legacy, base)?
+    };
+
+    let conn = database.connection()?;
+    let previous = conn
+        .query_row(
+            "SELECT id FROM library_roots WHERE id = ?1 OR path = ?2",
+            [&root_id, &library_root],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read library root: {}", err))?;
+    conn.execute("ATTACH DATABASE ?1 AS legacy", [normalize_path(copy)])
+        .map_err(|err| format!("Failed to attach {}: {}", copy.display(), err))?;
+
+    let result = (|| -> Result<DatabaseMigration, String> {
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Failed to start transaction: {}", err))?;
+        if let Some(previous) = previous {
+            libraries::delete_root(&tx, &previous)?;
+        }
+        libraries::delete_root(&tx, &root_id)?;
+        tx.execute(
+            "INSERT INTO library_roots (id, path, created_at) SELECT id, ?2, created_at FROM legacy.library_roots WHERE id = ?1",
+            [&root_id, &library_root],
+        )
+        .map_err(|err| format!("Failed to copy library root: {}", err))?;
+
+        for table in MIGRATED_TABLES {
+            let target = column_names(&tx, "main", table)?;
+            let source = column_names(&tx, "legacy", table)?;
+            // History ids are reassigned so they can't collide with other roots' revisions.
+            let columns = target
+                .into_iter()
+                .filter(|column| source.contains(column) && !(table == "manifest_history" && column == "id"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            tx.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO main.{table} ({columns}) SELECT {columns} FROM legacy.{table}"
+                ),
+                [],
+            )
+            .map_err(|err| format!("Failed to copy {}: {}", table, err))?;
+        }
+
+        let migration = DatabaseMigration {
+            entries: count_rows(&tx, "manifest_entries", &root_id)?,
+            play_history: count_rows(&tx, "play_history", &root_id)?,
+            watch_progress: count_rows(&tx, "watch_progress", &root_id)?,
+        };
+        tx.commit()
+            .map_err(|err| format!("Failed to commit transaction: {}", err))?;
+        Ok(migration)
+    })();
+    let _ = conn.execute("DETACH DATABASE legacy", []);
+    result
+}
+
+/// Deletes the index database an older version kept inside `base_dir`, along with its
+/// journal files. Only run on request, after `migrate_legacy_database` succeeded.
+#[tauri::command]
+pub fn remove_legacy_database(base_dir: String) -> Result<(), String> {
+    let legacy_path = legacy_database_path(Path::new(base_dir.trim()));
+    for path in database_files(&legacy_path) {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|err| format!("Failed to remove {}: {}", path.display(), err))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::database::Database;
+use crate::episodes::is_video_file;
+use crate::ignore::IGNORE_FILE_NAME;
+use crate::{history, libraries};
+use crate::identity::EntryMove;
+use crate::manifest::is_manifest_candidate;
+use crate::{is_index_database_file, normalize_path, refresh_index};
+
+/// Quiet period before a burst of file events is turned into a refresh.
+const DEBOUNCE: Duration = Duration::from_millis(800);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LibraryChangedEvent {
+    base_dir: String,
+    added: Vec<String>,
+    updated: Vec<String>,
+    removed: Vec<String>,
+    episodes_changed: Vec<String>,
+    moved: Vec<EntryMove>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LibraryWatchErrorEvent {
+    base_dir: String,
+    message: String,
+}
+
+struct ActiveWatcher {
+    base: PathBuf,
+    /// Started for a registered root, so it follows the registry rather than `unwatch_library`.
+    registered: bool,
+    // Dropping the watcher closes the event channel, which stops the worker thread.
+    _watcher: RecommendedWatcher,
+}
+
+/// Tauri-managed state holding one watcher per library root, keyed by root id.
+#[derive(Default)]
+pub struct LibraryWatcher {
+    active: Mutex<HashMap<String, ActiveWatcher>>,
+}
+
+impl LibraryWatcher {
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, ActiveWatcher>>, String> {
+        self.active
+            .lock()
+            .map_err(|_| "Library watcher state is poisoned".to_string())
+    }
+
+    fn start(&self, app: &AppHandle, id: &str, base: PathBuf, registered: bool) -> Result<(), String> {
+        let mut active = self.lock()?;
+        if let Some(current) = active.get_mut(id) {
+            if current.base == base {
+                current.registered |= registered;
+                return Ok(());
+            }
+        }
+        active.remove(id);
+
+        let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(tx)
+            .map_err(|err| format!("Failed to create watcher: {}", err))?;
+        watcher
+            .watch(&base, RecursiveMode::Recursive)
+            .map_err(|err| format!("Failed to watch {}: {}", base.display(), err))?;
+
+        let worker_app = app.clone();
+        let worker_base = base.clone();
+        thread::spawn(move || {
+            while let Ok(first) = rx.recv() {
+                let mut paths = Vec::<PathBuf>::new();
+                collect_paths(first, &mut paths);
+                loop {
+                    match rx.recv_timeout(DEBOUNCE) {
+                        Ok(event) => collect_paths(event, &mut paths),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                let scope = refresh_scope(&worker_base, paths);
+                if scope.is_empty() {
+                    continue;
+                }
+                handle_changes(&worker_app, &worker_base, &scope);
+            }
+        });
+
+        active.insert(
+            id.to_string(),
+            ActiveWatcher {
+                base,
+                registered,
+                _watcher: watcher,
+            },
+        );
+        Ok(())
+    }
+
+    /// Watches every enabled, reachable root in the registry and stops the watchers of roots
+    /// that were removed or disabled. A root whose folder moved is watched at its new path.
+    /// A root that can't be watched is reported with a `library-watch-error` event.
+    pub fn sync(&self, app: &AppHandle) -> Result<(), String> {
+        let roots = libraries::watched_roots(app)?;
+        self.lock()?
+            .retain(|id, watcher| !watcher.registered || roots.iter().any(|(root, _)| root == id));
+        for (id, base) in roots {
+            if let Err(message) = self.start(app, &id, base.clone(), true) {
+                let base_dir = normalize_path(&base);
+                let _ = app.emit("library-watch-error", LibraryWatchErrorEvent { base_dir, message });
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops the watchers started by `watch_library` for folders outside the registry.
+    fn stop_unregistered(&self) -> Result<(), String> {
+        self.lock()?.retain(|_, watcher| watcher.registered);
+        Ok(())
+    }
+}
+
+fn collect_paths(event: notify::Result<Event>, paths: &mut Vec<PathBuf>) {
+    if let Ok(event) = event {
+        if event.kind.is_access() {
+            return;
+        }
+        paths.extend(event.paths);
+    }
+}
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name().map(|name| name == IGNORE_FILE_NAME).unwrap_or(false)
+}
+
+/// Maps raw event paths to the folders that need rescanning.
+///
+/// Manifest, video and `.animeignore` edits rescan their folder, folder changes (and anything that no longer
+/// exists, since a deleted path may have been a folder) rescan that path. Other files are ignored.
+fn refresh_scope(base: &Path, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut scope = Vec::<PathBuf>::new();
+    for path in paths {
+        if !path.starts_with(base) || is_index_database_file(&path) || history::is_temp_file(&path) {
+            continue;
+        }
+        let target = if is_manifest_candidate(&path) || is_video_file(&path) || is_ignore_file(&path) {
+            match path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => continue,
+            }
+        } else if path.is_dir() || !path.exists() {
+            path
+        } else {
+            continue;
+        };
+        scope.push(target);
+    }
+
+    scope.sort();
+    scope.dedup();
+    let mut collapsed = Vec::<PathBuf>::new();
+    for path in scope {
+        if !collapsed.iter().any(|root| path.starts_with(root)) {
+            collapsed.push(path);
+        }
+    }
+    collapsed
+}
+
+fn handle_changes(app: &AppHandle, base: &Path, scope: &[PathBuf]) {
+    let base_dir = normalize_path(base);
+    let database = app.state::<Database>();
+    let settings = libraries::scan_settings(app, base);
+    match refresh_index(&database, base, scope, false, &settings, None) {
+        Ok(result) => {
+            let summary = result.summary;
+            if summary.added.is_empty()
+                && summary.updated.is_empty()
+                && summary.removed.is_empty()
+                && summary.episodes_changed.is_empty()
+                && summary.moved.is_empty()
+            {
+                return;
+            }
+            let _ = app.emit(
+                "library-changed",
+                LibraryChangedEvent {
+                    base_dir,
+                    added: summary.added,
+                    updated: summary.updated,
+                    removed: summary.removed,
+                    episodes_changed: summary.episodes_changed,
+                    moved: summary.moved,
+                },
+            );
+        }
+        Err(message) => {
+            let _ = app.emit("library-watch-error", LibraryWatchErrorEvent { base_dir, message });
+        }
+    }
+}
+
+/// Watches the library at `base_dir`. Registered roots are already watched through
+/// `LibraryWatcher::sync`; this covers a folder opened without registering it.
+#[tauri::command]
+pub fn watch_library(
+    app: AppHandle,
+    database: tauri::State<'_, Database>,
+    watcher: tauri::State<'_, LibraryWatcher>,
+    base_dir: String,
+) -> Result<(), String> {
+    let base = PathBuf::from(base_dir.trim());
+    if !base.exists() {
+        return Err(format!("Base directory not found: {}", base.display()));
+    }
+    let id = {
+        let conn = database.open(&base)?;
+        libraries::root_id(&conn, &normalize_path(&base))?
+    };
+    watcher.start(&app, &id, base, false)
+}
+
+#[tauri::command]
+pub fn unwatch_library(watcher: tauri::State<'_, LibraryWatcher>) -> Result<(), String> {
+    watcher.stop_unregistered()
+}
@@ -1,4 +1,5 @@
 import { FluentProvider, webDarkTheme, webLightTheme } from "@fluentui/react-components";
+import { listen } from "@tauri-apps/api/event";
 import { useCallback, useEffect, useMemo, useRef } from "react";
 import NavRail from "./components/NavRail";
 import { useDirectoryBrowser } from "./composables/useDirectoryBrowser";
@@ -12,9 +13,11 @@ import {
   createAnimeManifest,
   generateVideoIndexMarkdown,
   showErrorDialog,
+  unwatchLibrary,
   updateAnimeManifest,
+  watchLibrary,
 } from "./services/library";
-import type { NewAnimePayload } from "./types";
+import type { LibraryChangedEvent, NewAnimePayload } from "./types";
 
 export default function App() {
   const hasMountedRef = useRef(false);
@@ -53,6 +56,9 @@ export default function App() {
     loading,
     loadingAction,
     error,
+    diagnostics,
+    scanProgress,
+    skipped,
     selectedId,
     setSelectedId,
     selected,
@@ -69,6 +75,7 @@ export default function App() {
     filteredItems,
     loadLibraryEntries: reloadFromCache,
     refreshLibraryEntries: triggerRefresh,
+    cancelRefresh,
     toggleStatus,
     toggleFansub,
     toggleSubtitle,
@@ -156,6 +163,24 @@ export default function App() {
     };
   }, [settingsLoaded]);
 
+  useEffect(() => {
+    if (!settingsLoaded || !baseDir.trim()) return;
+    const timer = window.setTimeout(() => {
+      watchLibrary(baseDir.trim()).catch((err) => console.error(err));
+    }, 500);
+    return () => window.clearTimeout(timer);
+  }, [settingsLoaded, baseDir]);
+
+  useEffect(() => {
+    const unlisten = listen<LibraryChangedEvent>("library-changed", () => {
+      void reloadFromCache();
+    });
+    return () => {
+      void unlisten.then((dispose) => dispose());
+      void unwatchLibrary();
+    };
+  }, [reloadFromCache]);
+
   const resolvedTheme = useMemo(() => {
     if (themeMode === "dark") return webDarkTheme;
     if (themeMode === "light") return webLightTheme;
@@ -187,9 +212,9 @@ export default function App() {
   );
 
   const editAnime = useCallback(
-    async (entryPath: string, payload: NewAnimePayload) => {
+    async (entryPath: string, payload: NewAnimePayload, manifestRevision: string) => {
       try {
-        await updateAnimeManifest(entryPath, payload);
+        await updateAnimeManifest(baseDir.trim(), entryPath, payload, manifestRevision);
         await triggerRefresh();
       } catch (err) {
         const message = err instanceof Error ? err.message : String(err);
@@ -201,7 +226,7 @@ export default function App() {
         throw err;
       }
     },
-    [triggerRefresh]
+    [baseDir, triggerRefresh]
   );
 
   const generateIndex = useCallback(async () => {
@@ -237,11 +262,15 @@ export default function App() {
             autoRefresh={autoRefresh}
             loading={loading}
             loadingAction={loadingAction}
+            diagnostics={diagnostics}
+            scanProgress={scanProgress}
+            skipped={skipped}
             onBaseDirChange={setBaseDir}
             onThemeModeChange={setThemeMode}
             onAccentColorChange={setAccentColor}
             onAutoRefreshChange={setAutoRefresh}
             onRefresh={triggerRefresh}
+            onCancelRefresh={cancelRefresh}
             onLoad={reloadFromCache}
           />
 
@@ -1,6 +1,6 @@
 import { useCallback, useEffect, useMemo, useState } from "react";
-import { loadLibrary, refreshLibrary } from "../services/library";
-import type { LibraryEntry } from "../types";
+import { cancelScan, loadLibrary, refreshLibrary } from "../services/library";
+import type { LibraryEntry, ScanDiagnostic, ScanProgressEvent, SkippedPath } from "../types";
 
 export const useLibrary = (baseDir: string) => {
   const [search, setSearch] = useState("");
@@ -8,6 +8,9 @@ export const useLibrary = (baseDir: string) => {
   const [loading, setLoading] = useState(false);
   const [loadingAction, setLoadingAction] = useState<"load" | "refresh" | null>(null);
   const [error, setError] = useState("");
+  const [diagnostics, setDiagnostics] = useState<ScanDiagnostic[]>([]);
+  const [scanProgress, setScanProgress] = useState<ScanProgressEvent | null>(null);
+  const [skipped, setSkipped] = useState<SkippedPath[]>([]);
   const [selectedId, setSelectedId] = useState<string | null>(null);
 
   const [statusFilter, setStatusFilter] = useState<string[]>([]);
@@ -259,8 +262,9 @@ export const useLibrary = (baseDir: string) => {
     setLoading(true);
     setError("");
     try {
-      const results = await loadLibrary(baseDir.trim());
+      const { entries: results, diagnostics: problems } = await loadLibrary(baseDir.trim());
       setItems(results);
+      setDiagnostics(problems);
       setSelectedId((prev) => {
         if (prev && results.some((item) => item.id === prev)) {
           return prev;
@@ -282,8 +286,14 @@ export const useLibrary = (baseDir: string) => {
     setLoading(true);
     setError("");
     try {
-      const results = await refreshLibrary(baseDir.trim());
+      const {
+        entries: results,
+        diagnostics: problems,
+        skipped: ignored,
+      } = await refreshLibrary(baseDir.trim(), false, setScanProgress);
       setItems(results);
+      setDiagnostics(problems);
+      setSkipped(ignored);
       setSelectedId((prev) => {
         if (prev && results.some((item) => item.id === prev)) {
           return prev;
@@ -297,9 +307,16 @@ export const useLibrary = (baseDir: string) => {
     } finally {
       setLoading(false);
       setLoadingAction(null);
+      setScanProgress(null);
     }
   }, [baseDir]);
 
+  const cancelRefresh = useCallback(async () => {
+    if (scanProgress) {
+      await cancelScan(scanProgress.scanId);
+    }
+  }, [scanProgress]);
+
   return {
     search,
     setSearch,
@@ -308,6 +325,9 @@ export const useLibrary = (baseDir: string) => {
     loading,
     loadingAction,
     error,
+    diagnostics,
+    scanProgress,
+    skipped,
     selectedId,
     setSelectedId,
     selected,
@@ -324,6 +344,7 @@ export const useLibrary = (baseDir: string) => {
     groupedItems,
     loadLibraryEntries,
     refreshLibraryEntries,
+    cancelRefresh,
     toggleStatus,
     toggleFansub,
     toggleSubtitle,
@@ -45,7 +45,7 @@ interface DetailPageProps {
   onOpenEntry: (entry: FileEntry) => void;
   onPlayLast: () => void;
   onNavigateBreadcrumb: (path: string) => void;
-  onEditAnime: (entryPath: string, payload: NewAnimePayload) => Promise<void>;
+  onEditAnime: (entryPath: string, payload: NewAnimePayload, manifestRevision: string) => Promise<void>;
 }
 
 export default function DetailPage(props: DetailPageProps) {
@@ -118,15 +118,19 @@ export default function DetailPage(props: DetailPageProps) {
     setFormError("");
     setSaving(true);
     try {
-      await props.onEditAnime(props.selected.path, {
-        title: trimmedTitle,
-        fansub: fansub.trim(),
-        subtitleType: subtitleType.trim(),
-        quality: quality.trim(),
-        note: note.trim(),
-        isFinished,
-        episodes: parsedEpisodes,
-      });
+      await props.onEditAnime(
+        props.selected.path,
+        {
+          title: trimmedTitle,
+          fansub: fansub.trim(),
+          subtitleType: subtitleType.trim(),
+          quality: quality.trim(),
+          note: note.trim(),
+          isFinished,
+          episodes: parsedEpisodes,
+        },
+        props.selected.manifestRevision
+      );
       setEditOpen(false);
     } finally {
       setSaving(false);
@@ -200,6 +204,20 @@ export default function DetailPage(props: DetailPageProps) {
                       : "-"}
                   </dd>
                 </div>
+                <div className="detail-info-item">
+                  <dt>已有</dt>
+                  <dd>{props.selected ? `${props.selected.availableEpisodes} 集` : "-"}</dd>
+                </div>
+                <div className="detail-info-item">
+                  <dt>观看进度</dt>
+                  <dd>
+                    {props.selected
+                      ? `已看 ${props.selected.watchedEpisodes} 集${
+                          props.selected.nextEpisodeName ? `，下一集：${props.selected.nextEpisodeName}` : ""
+                        }`
+                      : "-"}
+                  </dd>
+                </div>
                 <div className="detail-info-item">
                   <dt>画质</dt>
                   <dd>{props.selected?.quality || "-"}</dd>
@@ -1,6 +1,7 @@
 import { Button, Card, Field, Input, Radio, RadioGroup, Spinner, Switch, Text, Title2 } from "@fluentui/react-components";
-import { ArrowClockwiseRegular, ArrowDownloadRegular } from "@fluentui/react-icons";
+import { ArrowClockwiseRegular, ArrowDownloadRegular, DismissRegular } from "@fluentui/react-icons";
 import type { ThemeMode } from "../composables/useSettings";
+import type { ScanDiagnostic, ScanProgressEvent, SkippedPath } from "../types";
 
 interface SettingsPageProps {
   active: boolean;
@@ -10,14 +11,45 @@ interface SettingsPageProps {
   autoRefresh: boolean;
   loading: boolean;
   loadingAction: "load" | "refresh" | null;
+  diagnostics: ScanDiagnostic[];
+  scanProgress: ScanProgressEvent | null;
+  skipped: SkippedPath[];
   onBaseDirChange: (value: string) => void;
   onThemeModeChange: (value: ThemeMode) => void;
   onAccentColorChange: (value: string) => void;
   onAutoRefreshChange: (value: boolean) => void;
   onRefresh: () => void;
+  onCancelRefresh: () => void;
   onLoad: () => void;
 }
 
+const diagnosticLabels: Record<ScanDiagnostic["kind"], string> = {
+  io: "读取失败",
+  encoding: "编码错误",
+  yaml: "格式错误",
+  link: "符号链接",
+  duplicate: "重复路径",
+  conflict: "多个清单",
+};
+
+const formatLocation = (item: ScanDiagnostic) =>
+  item.line !== null ? ` (第 ${item.line} 行${item.column !== null ? `，第 ${item.column} 列` : ""})` : "";
+
+const formatRuleSource = (item: SkippedPath) => {
+  if (item.source === "default") return "内置规则";
+  if (item.source === "library") return "资料库规则";
+  return `${item.source}${item.line !== null ? ` 第 ${item.line} 行` : ""}`;
+};
+
+const formatEta = (ms: number) => {
+  const seconds = Math.max(1, Math.round(ms / 1000));
+  return seconds < 60 ? `${seconds} 秒` : `${Math.floor(seconds / 60)} 分 ${seconds % 60} 秒`;
+};
+
+const formatProgress = (progress: ScanProgressEvent) =>
+  `已扫描 ${progress.directoriesVisited} 个文件夹，找到 ${progress.manifestsFound} 个清单` +
+  (progress.etaMs !== null ? `，预计还需 ${formatEta(progress.etaMs)}` : "");
+
 export default function SettingsPage(props: SettingsPageProps) {
   return (
     <section className={`page settings-page ${props.active ? "active" : ""}`} aria-hidden={!props.active}>
@@ -53,8 +85,18 @@ export default function SettingsPage(props: SettingsPageProps) {
               >
                 {props.loadingAction === "load" ? "正在读取缓存..." : "读取缓存"}
               </Button>
+              {props.loadingAction === "refresh" && (
+                <Button icon={<DismissRegular />} disabled={!props.scanProgress} onClick={props.onCancelRefresh}>
+                  取消扫描
+                </Button>
+              )}
             </div>
-            <Text size={200}>缓存数据库存放在库目录下的 anime-manager.sqlite。</Text>
+            {props.scanProgress && (
+              <Text size={200} title={props.scanProgress.currentPath}>
+                {formatProgress(props.scanProgress)}
+              </Text>
+            )}
+            <Text size={200}>缓存数据库存放在应用数据目录，不会写入库目录。</Text>
           </Card>
 
           <Card className="settings-card">
@@ -85,6 +127,43 @@ export default function SettingsPage(props: SettingsPageProps) {
             </div>
           </Card>
 
+          {props.diagnostics.length > 0 && (
+            <Card className="settings-card">
+              <Title2>扫描问题</Title2>
+              <Text size={200}>以下 manifest 或文件夹未能入库，已跳过（其余条目正常入库）。</Text>
+              <ul className="diagnostic-list">
+                {props.diagnostics.map((item) => (
+                  <li key={`${item.path}-${item.kind}`}>
+                    <Text weight="semibold">{diagnosticLabels[item.kind]}</Text>
+                    <Text size={200} className="diagnostic-path">
+                      {item.path}
+                      {formatLocation(item)}
+                    </Text>
+                    <Text size={200}>{item.message}</Text>
+                  </li>
+                ))}
+              </ul>
+            </Card>
+          )}
+
+          {props.skipped.length > 0 && (
+            <Card className="settings-card">
+              <Title2>已忽略的路径</Title2>
+              <Text size={200}>上次更新时按忽略规则跳过了以下路径（文件夹内的内容也一并跳过）。</Text>
+              <ul className="diagnostic-list">
+                {props.skipped.map((item) => (
+                  <li key={item.path}>
+                    <Text weight="semibold">{item.pattern}</Text>
+                    <Text size={200} className="diagnostic-path">
+                      {item.path}
+                    </Text>
+                    <Text size={200}>{formatRuleSource(item)}</Text>
+                  </li>
+                ))}
+              </ul>
+            </Card>
+          )}
+
           <Card className="settings-card">
             <Title2>更新策略</Title2>
             <Switch
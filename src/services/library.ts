@@ -1,18 +1,216 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { FileEntry, LibraryEntry, NewAnimePayload } from "../types";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type {
+  AcceptedManifest,
+  DatabaseLocation,
+  DatabaseMigration,
+  EntryIntegrity,
+  EpisodeFile,
+  FileEntry,
+  IntegrityReport,
+  LibraryPage,
+  LibraryQuery,
+  LibraryRoot,
+  LibraryRootUpdate,
+  LibrarySnapshot,
+  ManifestFormat,
+  ManifestProposal,
+  ManifestRevision,
+  ManifestValidation,
+  NewAnimePayload,
+  RefreshResult,
+  ScanCompletedEvent,
+  ScanFinishedEvent,
+  ScanProgressEvent,
+  SearchHit,
+  SkippedPath,
+} from "../types";
 
-export async function loadLibrary(baseDir: string): Promise<LibraryEntry[]> {
-  return await invoke<LibraryEntry[]>("load_library", { baseDir });
+export async function loadLibrary(baseDir: string): Promise<LibrarySnapshot> {
+  return await invoke<LibrarySnapshot>("load_library", { baseDir });
 }
 
-export async function refreshLibrary(baseDir: string): Promise<LibraryEntry[]> {
-  return await invoke<LibraryEntry[]>("refresh_library", { baseDir });
+export async function loadAllLibraries(): Promise<LibrarySnapshot> {
+  return await invoke<LibrarySnapshot>("load_library");
+}
+
+export async function listLibraries(): Promise<LibraryRoot[]> {
+  return await invoke<LibraryRoot[]>("list_libraries");
+}
+
+export async function addLibrary(path: string, name?: string): Promise<LibraryRoot> {
+  return await invoke<LibraryRoot>("add_library", { path, name });
+}
+
+export async function updateLibrary(id: string, update: LibraryRootUpdate): Promise<LibraryRoot> {
+  return await invoke<LibraryRoot>("update_library", { id, update });
+}
+
+export async function removeLibrary(id: string): Promise<void> {
+  await invoke<void>("remove_library", { id });
+}
+
+export async function getDatabaseLocation(): Promise<DatabaseLocation> {
+  return await invoke<DatabaseLocation>("get_database_location");
+}
+
+export async function setDatabaseLocation(directory?: string): Promise<DatabaseLocation> {
+  return await invoke<DatabaseLocation>("set_database_location", { directory });
+}
+
+export async function hasLegacyDatabase(baseDir: string): Promise<boolean> {
+  return await invoke<boolean>("has_legacy_database", { baseDir });
+}
+
+export async function migrateLegacyDatabase(baseDir: string): Promise<DatabaseMigration> {
+  return await invoke<DatabaseMigration>("migrate_legacy_database", { baseDir });
+}
+
+export async function removeLegacyDatabase(baseDir: string): Promise<void> {
+  await invoke("remove_legacy_database", { baseDir });
+}
+
+export async function startScan(baseDir: string, fullRescan = false): Promise<string> {
+  return await invoke<string>("refresh_library", { baseDir, fullRescan });
+}
+
+export async function cancelScan(scanId: string): Promise<boolean> {
+  return await invoke<boolean>("cancel_scan", { scanId });
+}
+
+/** Runs a background scan and resolves with its result once the `scan-completed` event arrives. */
+export async function refreshLibrary(
+  baseDir: string,
+  fullRescan = false,
+  onProgress?: (progress: ScanProgressEvent) => void,
+): Promise<RefreshResult> {
+  const unlisteners: UnlistenFn[] = [];
+  let scanId: string | null = null;
+  try {
+    return await new Promise<RefreshResult>((resolve, reject) => {
+      // Listen before starting so a fast scan can't finish unobserved; events from other
+      // scans are filtered out once the id is known.
+      const pending: Array<() => void> = [];
+      const forScan = <T extends { scanId: string }>(handler: (payload: T) => void) => (event: { payload: T }) => {
+        const run = () => {
+          if (event.payload.scanId === scanId) handler(event.payload);
+        };
+        if (scanId === null) pending.push(run);
+        else run();
+      };
+      Promise.all([
+        listen<ScanProgressEvent>("scan-progress", forScan((payload) => onProgress?.(payload))),
+        listen<ScanCompletedEvent>("scan-completed", forScan((payload) => resolve(payload.result))),
+        listen<ScanFinishedEvent>("scan-cancelled", forScan((payload) => reject(new Error(payload.message)))),
+        listen<ScanFinishedEvent>("scan-failed", forScan((payload) => reject(new Error(payload.message)))),
+      ])
+        .then((disposers) => {
+          unlisteners.push(...disposers);
+          return startScan(baseDir, fullRescan);
+        })
+        .then((id) => {
+          scanId = id;
+          pending.splice(0).forEach((run) => run());
+        })
+        .catch(reject);
+    });
+  } finally {
+    unlisteners.forEach((dispose) => dispose());
+  }
+}
+
+export async function explainIgnored(path: string): Promise<SkippedPath | null> {
+  return await invoke<SkippedPath | null>("explain_ignored", { path });
+}
+
+export async function watchLibrary(baseDir: string): Promise<void> {
+  await invoke<void>("watch_library", { baseDir });
+}
+
+export async function unwatchLibrary(): Promise<void> {
+  await invoke<void>("unwatch_library");
+}
+
+export async function searchLibrary(baseDir: string, query: string, limit?: number): Promise<SearchHit[]> {
+  return await invoke<SearchHit[]>("search_library", { baseDir, query, limit: limit ?? null });
+}
+
+export async function queryLibrary(baseDir: string, query: LibraryQuery): Promise<LibraryPage> {
+  return await invoke<LibraryPage>("query_library", { baseDir, query });
+}
+
+export async function validateManifests(baseDir: string): Promise<ManifestValidation[]> {
+  return await invoke<ManifestValidation[]>("validate_manifests", { baseDir });
+}
+
+export async function migrateManifests(baseDir: string): Promise<string[]> {
+  return await invoke<string[]>("migrate_manifests", { baseDir });
+}
+
+/** Rewrites a manifest in another format and returns the new file's path. */
+export async function convertManifest(
+  baseDir: string,
+  manifestPath: string,
+  format: ManifestFormat
+): Promise<string> {
+  return await invoke<string>("convert_manifest", { baseDir, manifestPath, format });
+}
+
+/** Guesses manifests for folders that have videos but no manifest; nothing is written. */
+export async function discoverManifests(baseDir: string): Promise<ManifestProposal[]> {
+  return await invoke<ManifestProposal[]>("discover_manifests", { baseDir });
+}
+
+/** Writes the reviewed proposals as `manifest.yml` files and returns the paths written. */
+export async function writeDiscoveredManifests(
+  baseDir: string,
+  manifests: AcceptedManifest[]
+): Promise<string[]> {
+  return await invoke<string[]>("write_discovered_manifests", { baseDir, manifests });
 }
 
 export async function listDirectory(path: string): Promise<FileEntry[]> {
   return await invoke<FileEntry[]>("list_directory", { path });
 }
 
+export async function listEpisodes(baseDir: string, entryId: string): Promise<EpisodeFile[]> {
+  return await invoke<EpisodeFile[]>("list_episodes", { baseDir, entryId });
+}
+
+export async function checkEntryIntegrity(baseDir: string, entryId: string): Promise<EntryIntegrity> {
+  return await invoke<EntryIntegrity>("check_entry_integrity", { baseDir, entryId });
+}
+
+export async function checkLibraryIntegrity(baseDir: string): Promise<IntegrityReport> {
+  return await invoke<IntegrityReport>("check_library_integrity", { baseDir });
+}
+
+export async function markEpisodeWatched(
+  baseDir: string,
+  entryId: string,
+  filePath: string,
+  watched: boolean
+): Promise<void> {
+  await invoke<void>("mark_episode_watched", { baseDir, entryId, filePath, watched });
+}
+
+export async function markEpisodesWatchedThrough(baseDir: string, entryId: string, episode: number): Promise<number> {
+  return await invoke<number>("mark_episodes_watched_through", { baseDir, entryId, episode });
+}
+
+export async function resetWatchProgress(baseDir: string, entryId: string, filePath?: string): Promise<void> {
+  await invoke<void>("reset_watch_progress", { baseDir, entryId, filePath: filePath ?? null });
+}
+
+export async function updatePlaybackPosition(
+  baseDir: string,
+  entryId: string,
+  filePath: string,
+  positionSeconds: number
+): Promise<void> {
+  await invoke<void>("update_playback_position", { baseDir, entryId, filePath, positionSeconds });
+}
+
 export async function updatePlayHistory(
   baseDir: string,
   entryId: string,
@@ -46,13 +244,33 @@ export async function createAnimeManifest(baseDir: string, payload: NewAnimePayl
       note: payload.note,
       isFinished: payload.isFinished,
       episodes: payload.episodes,
+      aliases: payload.aliases,
+      originalTitle: payload.originalTitle,
+      year: payload.year,
+      season: payload.season,
+      studio: payload.studio,
+      tags: payload.tags,
+      source: payload.source,
+      rating: payload.rating,
+      externalIds: payload.externalIds,
     },
   });
 }
 
-export async function updateAnimeManifest(entryPath: string, payload: NewAnimePayload): Promise<string> {
+/**
+ * `expectedRevision` is the entry's `manifestRevision`; the edit is rejected if the file
+ * changed on disk since it was indexed.
+ */
+export async function updateAnimeManifest(
+  baseDir: string,
+  entryPath: string,
+  payload: NewAnimePayload,
+  expectedRevision?: string
+): Promise<string> {
   return await invoke<string>("update_anime_manifest", {
+    baseDir,
     entryPath,
+    expectedRevision: expectedRevision ?? null,
     payload: {
       title: payload.title,
       fansub: payload.fansub,
@@ -61,10 +279,27 @@ export async function updateAnimeManifest(entryPath: string, payload: NewAnimePa
       note: payload.note,
       isFinished: payload.isFinished,
       episodes: payload.episodes,
+      aliases: payload.aliases,
+      originalTitle: payload.originalTitle,
+      year: payload.year,
+      season: payload.season,
+      studio: payload.studio,
+      tags: payload.tags,
+      source: payload.source,
+      rating: payload.rating,
+      externalIds: payload.externalIds,
     },
   });
 }
 
+export async function listManifestHistory(baseDir: string, entryId: string): Promise<ManifestRevision[]> {
+  return await invoke<ManifestRevision[]>("list_manifest_history", { baseDir, entryId });
+}
+
+export async function revertManifest(baseDir: string, entryId: string, revisionId: number): Promise<string> {
+  return await invoke<string>("revert_manifest", { baseDir, entryId, revisionId });
+}
+
 export async function showErrorDialog(title: string, message: string): Promise<void> {
   await invoke<void>("show_error_dialog", { title, message });
 }
@@ -1,5 +1,7 @@
 export interface LibraryEntry {
   id: string;
+  libraryId: string;
+  uid: string;
   title: string;
   fansub: string;
   subtitleType: string;
@@ -13,6 +15,195 @@ export interface LibraryEntry {
   lastPlayedPath: string;
   lastPlayedName: string;
   lastPlayedAt: number;
+  availableEpisodes: number;
+  /** Total size of the entry's video files in bytes. */
+  totalSize: number;
+  addedAt: number;
+  watchedEpisodes: number;
+  nextEpisodePath: string;
+  nextEpisodeName: string;
+  nextEpisodeNumber: number | null;
+  /** Content hash of `manifest.yml` when indexed; pass back when editing. */
+  manifestRevision: string;
+  aliases: string[];
+  originalTitle: string;
+  year: number | null;
+  /** Broadcast season as `YYYY-MM` when recognized. */
+  season: string;
+  studio: string;
+  tags: string[];
+  /** `BD` / `WEB` / `TV` / `DVD` when recognized. */
+  source: string;
+  rating: number | null;
+  /** Provider (`anilist`, `bangumi`, `mal`, ...) to id. */
+  externalIds: Record<string, string>;
+}
+
+export type EpisodeKind = "episode" | "special" | "ova" | "opening" | "ending";
+
+export interface EpisodeFile {
+  path: string;
+  fileName: string;
+  relativePath: string;
+  episode: number | null;
+  season: number | null;
+  kind: EpisodeKind;
+  size: number;
+  modifiedAt: number;
+  watched: boolean;
+  watchCount: number;
+  lastWatchedAt: number;
+  positionSeconds: number | null;
+}
+
+/** Files without a season marker count as season 1. */
+export interface EpisodeNumber {
+  season: number;
+  episode: number;
+}
+
+export interface DuplicateEpisode {
+  season: number;
+  episode: number;
+  files: string[];
+}
+
+export interface EntryIntegrity {
+  entryId: string;
+  title: string;
+  declared: number;
+  present: number;
+  missing: EpisodeNumber[];
+  duplicates: DuplicateEpisode[];
+  extras: EpisodeNumber[];
+  unnumbered: string[];
+}
+
+export interface IntegrityReport {
+  checkedEntries: number;
+  missingEpisodes: number;
+  duplicateEpisodes: number;
+  extraEpisodes: number;
+  entries: EntryIntegrity[];
+}
+
+export interface SearchHit {
+  entryId: string;
+  title: string;
+  /** HTML-escaped title with matches wrapped in `<mark>`; `snippet` is escaped the same way. */
+  titleHighlight: string;
+  snippet: string;
+  rank: number;
+}
+
+export type EntryStatus = "finished" | "ongoing" | "unknown";
+
+export interface LibraryFilter {
+  fansubs?: string[];
+  qualities?: string[];
+  groups?: string[];
+  status?: EntryStatus[];
+  hasUnwatched?: boolean;
+  minSize?: number;
+  maxSize?: number;
+  /** Unix timestamp in seconds. */
+  addedAfter?: number;
+}
+
+export type SortKey = "title" | "lastPlayed" | "added" | "size" | "episodes" | "available";
+
+export interface SortOrder {
+  key: SortKey;
+  descending?: boolean;
+}
+
+export interface LibraryQuery {
+  filter?: LibraryFilter;
+  sort?: SortOrder[];
+  offset?: number;
+  limit?: number;
+}
+
+export interface LibraryPage {
+  entries: LibraryEntry[];
+  total: number;
+  offset: number;
+  limit: number;
+}
+
+export interface RefreshSummary {
+  added: string[];
+  updated: string[];
+  removed: string[];
+  unchanged: number;
+  episodesChanged: string[];
+  moved: EntryMove[];
+}
+
+export interface EntryMove {
+  from: string;
+  to: string;
+}
+
+export type DiagnosticKind = "io" | "encoding" | "yaml" | "link" | "duplicate" | "conflict";
+
+export interface ScanDiagnostic {
+  path: string;
+  kind: DiagnosticKind;
+  message: string;
+  line: number | null;
+  column: number | null;
+}
+
+export interface RefreshResult {
+  entries: LibraryEntry[];
+  summary: RefreshSummary;
+  diagnostics: ScanDiagnostic[];
+  skipped: SkippedPath[];
+}
+
+export interface SkippedPath {
+  path: string;
+  pattern: string;
+  /** The `.animeignore` file the pattern is from, or `default` / `library`. */
+  source: string;
+  line: number | null;
+}
+
+export interface LibrarySnapshot {
+  entries: LibraryEntry[];
+  diagnostics: ScanDiagnostic[];
+}
+
+export interface ScanProgressEvent {
+  scanId: string;
+  baseDir: string;
+  directoriesVisited: number;
+  manifestsFound: number;
+  currentPath: string;
+  elapsedMs: number;
+  etaMs: number | null;
+}
+
+export interface ScanCompletedEvent {
+  scanId: string;
+  baseDir: string;
+  result: RefreshResult;
+}
+
+export interface ScanFinishedEvent {
+  scanId: string;
+  baseDir: string;
+  message: string;
+}
+
+export interface LibraryChangedEvent {
+  baseDir: string;
+  added: string[];
+  updated: string[];
+  removed: string[];
+  episodesChanged: string[];
+  moved: EntryMove[];
 }
 
 export interface FileEntry {
@@ -39,4 +230,102 @@ export interface NewAnimePayload {
   note: string;
   isFinished: boolean;
   episodes: number;
+  /** Omitted fields keep the manifest's current value; empty values (or 0) remove it. */
+  aliases?: string[];
+  originalTitle?: string;
+  year?: number;
+  season?: string;
+  studio?: string;
+  tags?: string[];
+  source?: string;
+  rating?: number;
+  externalIds?: Record<string, string>;
+}
+
+/** A manifest guessed from release names such as `[Group] Title [01-12][1080p][CHS]`. */
+export interface ManifestProposal {
+  path: string;
+  title: string;
+  fansub: string;
+  subtitleType: string;
+  quality: string;
+  episodes: number;
+  isFinished: boolean;
+  videoFiles: number;
+  sampleFile: string;
+}
+
+export interface AcceptedManifest {
+  path: string;
+  payload: NewAnimePayload;
+}
+
+export type ManifestIssueKind = "deprecated" | "misspelled" | "unknown" | "unsupportedVersion" | "invalid";
+
+export interface ManifestIssue {
+  kind: ManifestIssueKind;
+  key: string | null;
+  suggestion: string | null;
+  message: string;
+}
+
+export type ManifestFormat = "yaml" | "json" | "toml";
+
+export interface ManifestValidation {
+  path: string;
+  schemaVersion: number;
+  outdated: boolean;
+  issues: ManifestIssue[];
+}
+
+export interface ManifestRevision {
+  id: number;
+  manifestPath: string;
+  content: string;
+  contentHash: string;
+  savedAt: number;
+}
+
+export interface LibraryRoot {
+  id: string;
+  name: string;
+  path: string;
+  enabled: boolean;
+  addedAt: number;
+  /** Threads used to read manifests and size folders; null uses the default. */
+  scanConcurrency: number | null;
+  ignorePatterns: string[];
+  followSymlinks: boolean;
+  sameFileSystem: boolean;
+  maxDepth: number | null;
+  /** Also index `.anime.yml` / `.anime.yaml`. */
+  hiddenManifests: boolean;
+  available: boolean;
+}
+
+export interface LibraryRootUpdate {
+  name?: string;
+  path?: string;
+  enabled?: boolean;
+  /** 0 goes back to the default; 1 suits libraries on spinning disks. */
+  scanConcurrency?: number;
+  ignorePatterns?: string[];
+  followSymlinks?: boolean;
+  sameFileSystem?: boolean;
+  /** 0 removes the limit. */
+  maxDepth?: number;
+  hiddenManifests?: boolean;
+}
+
+export interface DatabaseLocation {
+  path: string;
+  directory: string;
+  defaultDirectory: string;
+  isDefault: boolean;
+}
+
+export interface DatabaseMigration {
+  entries: number;
+  playHistory: number;
+  watchProgress: number;
 }